@@ -1,7 +1,12 @@
-use clap::{Parser, ValueEnum};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use color_eyre::Result;
 use color_eyre::eyre::bail;
+use rustwell::diagnostic::Diagnostic;
+use rustwell::screenplay::{Element, Screenplay};
+use rustwell::stats::Stats;
+use rustwell::{ExportOptions, Exporter, Layout, NoteStyle, PaperSize, ParseOptions, Theme};
 
+use std::ffi::OsString;
 use std::fs::File;
 use std::io;
 use std::io::BufReader;
@@ -12,79 +17,656 @@ use std::path::Path;
 
 #[derive(Debug, Parser)]
 #[command(name = "rustwell", version, about = "A Fountain compiler CLI")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Source file (use '-' for stdin)
-    src: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Source file (use '-' for stdin, and '-- <SRC>' for a file named like a command)
+    #[arg(required = true)]
+    src: Option<String>,
 
     /// Output path (use '-' for stdout)
     #[arg(short = 'o', long = "out")]
     out: Option<String>,
 
-    /// Explicit target format (overrides detection)
-    #[arg(short = 't', long = "target", value_enum)]
-    target: Option<Target>,
+    /// Explicit target format, like pdf, html, typst, fountain or json (overrides detection)
+    #[arg(short = 't', long = "target")]
+    target: Option<String>,
+
+    /// Explicit source format (overrides detection, defaults to fountain)
+    #[arg(short = 'f', long = "from", value_enum)]
+    from: Option<Source>,
 
     /// Alias for stdout (same as `-o -`)
     #[arg(long = "stdout")]
     stdout: bool,
 
+    #[command(flatten)]
+    export: ExportArgs,
+
+    /// Number all scenes, keeping locked scene numbers
+    #[arg(long = "number-scenes", default_value_t = false)]
+    number_scenes: bool,
+}
+
+impl Cli {
+    /// The source file, which is always given when there is no subcommand.
+    fn src(&self) -> &str {
+        self.src.as_deref().unwrap_or("-")
+    }
+}
+
+/// The flags shaping the exported document, shared by exporting and `stats`.
+#[derive(Debug, Args)]
+struct ExportArgs {
     /// Include synopses in output document
     #[arg(short = 's', long = "synopses", default_value_t = false)]
     synopses: bool,
+
+    /// How to render notes in output document
+    #[arg(short = 'n', long = "notes", value_enum, default_value_t = Notes::Hidden)]
+    notes: Notes,
+
+    /// Show cut material (boneyards) greyed out in output document
+    #[arg(short = 'c', long = "cuts", default_value_t = false)]
+    cuts: bool,
+
+    /// Include section titles in output document
+    #[arg(long = "sections", default_value_t = false)]
+    sections: bool,
+
+    /// Leave scene numbers out of output document
+    #[arg(long = "no-scene-numbers", default_value_t = false)]
+    no_scene_numbers: bool,
+
+    /// Paper size of printed documents: letter, a4, or a custom size like 148x210mm
+    #[arg(long = "paper", value_parser = parse_paper, default_value = "letter")]
+    paper: PaperSize,
+
+    /// Font family of printed documents, falling back to Courier Prime for missing glyphs
+    #[arg(long = "font")]
+    font: Option<String>,
+
+    /// Font file (ttf, otf, ttc or otc), or directory of them, to load for printed documents
+    #[arg(long = "font-path")]
+    font_paths: Vec<String>,
+
+    /// Font family to try for glyphs missing in the chosen font and Courier Prime, replacing the
+    /// default fallback fonts when given
+    #[arg(long = "fallback-font")]
+    fallback_fonts: Vec<String>,
+
+    /// Search the fonts installed on the system for the chosen and fallback fonts
+    #[arg(long = "system-fonts", default_value_t = false)]
+    system_fonts: bool,
+
+    /// Stylesheet to use instead of the default one in html documents
+    #[arg(long = "css")]
+    css: Option<String>,
+
+    /// Typst template to use instead of the default one in printed documents
+    #[arg(long = "template")]
+    template: Option<String>,
+
+    /// Read short lines without case or punctuation, like Japanese or Hebrew names, as character
+    /// cues
+    #[arg(long = "caseless-cues", default_value_t = false)]
+    caseless_cues: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Rewrite Fountain files in a canonical style
+    Fmt(FmtArgs),
+    /// Print statistics of a screenplay, like word counts and how much each character speaks
+    Stats(StatsArgs),
+}
+
+#[derive(Debug, Args)]
+struct FmtArgs {
+    /// Files to format (use '-' for stdin, written to stdout)
+    #[arg(required = true)]
+    files: Vec<String>,
+
+    /// Only check that the files are formatted, failing if any of them is not
+    #[arg(long = "check", default_value_t = false)]
+    check: bool,
+
+    /// Number all scenes, keeping locked scene numbers
+    #[arg(long = "number-scenes", default_value_t = false)]
+    number_scenes: bool,
+
+    /// Number all scenes and lock the numbers, so that later scenes are numbered like 12A
+    #[arg(long = "lock-scenes", default_value_t = false)]
+    lock_scenes: bool,
+
+    /// Read short lines without case or punctuation, like Japanese or Hebrew names, as character
+    /// cues
+    #[arg(long = "caseless-cues", default_value_t = false)]
+    caseless_cues: bool,
+}
+
+#[derive(Debug, Args)]
+struct StatsArgs {
+    /// Source file (use '-' for stdin)
+    src: String,
+
+    /// Explicit source format (overrides detection, defaults to fountain)
+    #[arg(short = 'f', long = "from", value_enum)]
+    from: Option<Source>,
+
+    /// Print the statistics as JSON instead of a table
+    #[arg(long = "json", default_value_t = false)]
+    json: bool,
+
+    /// Lay out the screenplay like the pdf, to count its pages and measure its scenes in eighths
+    #[arg(short = 'p', long = "pages", default_value_t = false)]
+    pages: bool,
+
+    #[command(flatten)]
+    export: ExportArgs,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Source {
+    Fountain,
+    Json,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
-enum Target {
-    Typst,
-    Html,
-    Pdf,
+enum Notes {
+    Hidden,
+    Highlighted,
+    Margin,
+    Footnotes,
+}
+
+/// The [Exporter] for JSON, in the shape documented in [rustwell::screenplay]. It is
+/// implemented here rather than in Rustwell, as the library only derives the `serde` traits.
+struct JsonExporter;
+
+impl Exporter for JsonExporter {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["json"]
+    }
+
+    fn export(
+        &self,
+        screenplay: &Screenplay,
+        writer: &mut dyn Write,
+        _options: &ExportOptions,
+    ) -> rustwell::Result<()> {
+        serde_json::to_writer_pretty(&mut *writer, screenplay).map_err(io::Error::from)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+impl From<Notes> for NoteStyle {
+    fn from(notes: Notes) -> Self {
+        match notes {
+            Notes::Hidden => NoteStyle::Hidden,
+            Notes::Highlighted => NoteStyle::Highlighted,
+            Notes::Margin => NoteStyle::Margin,
+            Notes::Footnotes => NoteStyle::Footnotes,
+        }
+    }
 }
 
 fn main() -> Result<()> {
     color_eyre::install()?;
-    let cli = Cli::parse();
+    let args = std::env::args_os().collect::<Vec<OsString>>();
+    let cli = parse_cli(&args, |path| Path::new(path).is_file()).unwrap_or_else(|e| e.exit());
+    match &cli.command {
+        Some(Command::Fmt(args)) => return fmt(args),
+        Some(Command::Stats(args)) => return stats(args),
+        None => (),
+    }
 
     let mut reader = decide_reader(&cli)?;
-    let target = decide_target(&cli);
-    let mut writer = decide_writer(&cli, &target)?;
-
     let mut buf = String::new();
     reader.read_to_string(&mut buf)?;
 
-    let screenplay = rustwell::parse(&buf);
-    match target {
-        Target::Html => rustwell::export_html(&screenplay, &mut writer, true, cli.synopses),
-        Target::Pdf => rustwell::export_pdf(&screenplay, &mut writer, cli.synopses),
-        Target::Typst => rustwell::export_typst(&screenplay, &mut writer, cli.synopses),
+    let exporters = exporters();
+    let exporter = decide_target(&cli, &exporters)?;
+    let options = decide_options(&cli.export)?;
+    let mut writer = decide_writer(&cli, exporter)?;
+
+    let mut screenplay: Screenplay = match decide_source(&cli) {
+        Source::Json => serde_json::from_str(&buf)?,
+        Source::Fountain => {
+            let options = ParseOptions {
+                boneyards: cli.export.cuts,
+                caseless_cues: cli.export.caseless_cues,
+            };
+            let (screenplay, diagnostics) = rustwell::parse_with_diagnostics(&buf, options);
+            for diagnostic in &diagnostics {
+                eprintln!(
+                    "{}",
+                    format_diagnostic(source_name(cli.src()), &buf, diagnostic)
+                );
+            }
+            screenplay
+        }
+    };
+    if cli.number_scenes {
+        let unnumbered = screenplay.number_scenes();
+        warn_unnumbered(source_name(cli.src()), &screenplay, &unnumbered);
+    }
+    let missing = exporter.missing_glyphs(&screenplay, &options)?;
+    if !missing.is_empty() {
+        let missing: String = missing.into_iter().collect();
+        eprintln!("warning: no font has glyphs for '{missing}', which will be left blank");
+        eprintln!("hint: use --system-fonts, or load a font with them with --font-path");
+    }
+    exporter.export(&screenplay, &mut writer, &options)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Parses the [Cli] from `args`. A source file named like a command, like `fmt`, is exported
+/// if the arguments are not valid for that command and the file `exists`, as if it was given
+/// after `--`.
+fn parse_cli(args: &[OsString], exists: impl Fn(&str) -> bool) -> clap::error::Result<Cli> {
+    let error = match Cli::try_parse_from(args) {
+        Ok(cli) => return Ok(cli),
+        Err(error) => error,
+    };
+
+    let commands = Cli::command();
+    let named_file = args.iter().enumerate().skip(1).find_map(|(i, arg)| {
+        let arg = arg.to_str()?;
+        let command = commands.find_subcommand(arg).is_some();
+        (command && exists(arg)).then_some(i)
+    });
+    let Some(i) = named_file else {
+        return Err(error);
+    };
+    let mut escaped = args.to_vec();
+    let file = escaped.remove(i);
+    escaped.extend([OsString::from("--"), file]);
+    Cli::try_parse_from(escaped).map_err(|_| error)
+}
+
+/// Formats the files of `fmt`, or checks that they already are formatted.
+///
+/// A file that can not be read, formatted or written is reported and skipped, so that the rest
+/// of the files are still formatted, and `fmt` fails once all of them are done.
+fn fmt(args: &FmtArgs) -> Result<()> {
+    let (mut unformatted, mut failed) = (0, 0);
+    for file in &args.files {
+        match fmt_file(file, args) {
+            Ok(true) => (),
+            Ok(false) => unformatted += 1,
+            Err(error) => {
+                eprintln!("error: {}: {error}", source_name(file));
+                failed += 1;
+            }
+        }
     }
 
+    if failed > 0 {
+        bail!("{failed} file(s) could not be formatted");
+    }
+    if unformatted > 0 {
+        bail!("{unformatted} file(s) would be reformatted");
+    }
     Ok(())
 }
 
+/// Formats a single file of `fmt`, or checks that it already is formatted, returning if it was.
+///
+/// The file is parsed, keeping notes, boneyards and sections, and written back as canonical
+/// *Fountain*. Transitions are tidied up, as they are always printed in upper case anyway.
+/// The file is left untouched if it could not be written back without changing its contents.
+fn fmt_file(file: &str, args: &FmtArgs) -> Result<bool> {
+    let name = source_name(file);
+    let src = read_source(file)?;
+
+    let options = ParseOptions {
+        boneyards: true,
+        caseless_cues: args.caseless_cues,
+    };
+    let (mut screenplay, diagnostics) = rustwell::parse_with_diagnostics(&src, options);
+    for diagnostic in &diagnostics {
+        eprintln!("{}", format_diagnostic(name, &src, diagnostic));
+    }
+    tidy_transitions(&mut screenplay);
+    let unnumbered = if args.lock_scenes {
+        screenplay.lock_scene_numbers()
+    } else if args.number_scenes {
+        screenplay.number_scenes()
+    } else {
+        Vec::new()
+    };
+    warn_unnumbered(name, &screenplay, &unnumbered);
+
+    let mut out = Vec::new();
+    rustwell::export_fountain(&screenplay, &mut out)?;
+    let formatted = String::from_utf8(out)?;
+    if rustwell::parse_with_options(&formatted, options) != screenplay {
+        bail!("could not format without changing its contents");
+    }
+
+    if args.check {
+        if let Some(line) = first_difference(&src, &formatted) {
+            eprintln!("{name}:{line}: not formatted");
+            return Ok(false);
+        }
+    } else if file == "-" {
+        io::stdout().write_all(formatted.as_bytes())?;
+    } else if formatted != src {
+        std::fs::write(file, formatted)?;
+    }
+    Ok(true)
+}
+
+/// Warns about the scenes left without a number, as no number fits between the locked numbers
+/// of the scenes around them.
+fn warn_unnumbered(name: &str, screenplay: &Screenplay, unnumbered: &[usize]) {
+    let scenes = screenplay.scenes();
+    for &i in unnumbered {
+        eprintln!(
+            "warning: {name}: no scene number fits between the locked numbers around '{}'",
+            scenes[i].heading.plain_text()
+        );
+    }
+}
+
+/// Prints the [Stats] of a screenplay as a table, or as JSON.
+fn stats(args: &StatsArgs) -> Result<()> {
+    let src = read_source(&args.src)?;
+    let screenplay: Screenplay = match args.from.unwrap_or(detect_source_from_path(&args.src)) {
+        Source::Json => serde_json::from_str(&src)?,
+        Source::Fountain => {
+            let options = ParseOptions {
+                boneyards: args.export.cuts,
+                caseless_cues: args.export.caseless_cues,
+            };
+            let (screenplay, diagnostics) = rustwell::parse_with_diagnostics(&src, options);
+            for diagnostic in &diagnostics {
+                eprintln!(
+                    "{}",
+                    format_diagnostic(source_name(&args.src), &src, diagnostic)
+                );
+            }
+            screenplay
+        }
+    };
+
+    let stats = Stats::new(&screenplay);
+    let layout = if args.pages {
+        Some(Layout::new(&screenplay, &decide_options(&args.export)?)?)
+    } else {
+        None
+    };
+
+    let mut writer = BufWriter::new(io::stdout());
+    if args.json {
+        let mut value = serde_json::to_value(&stats)?;
+        if let Some(layout) = &layout {
+            value["pages"] = layout.pages.into();
+            value["eighths"] = layout.eighths().into();
+        }
+        serde_json::to_writer_pretty(&mut writer, &value)?;
+        writeln!(writer)?;
+    } else {
+        write_stats_table(&mut writer, &stats)?;
+        if let Some(layout) = &layout {
+            write_layout_table(&mut writer, &screenplay, layout)?;
+        }
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes the [Stats] as aligned columns of plain text.
+fn write_stats_table(mut writer: impl Write, stats: &Stats) -> Result<()> {
+    let width = stats
+        .characters
+        .iter()
+        .map(|c| c.name.chars().count())
+        .chain([16])
+        .max()
+        .unwrap_or_default()
+        + 2;
+    let row = |name: &str, value: usize| format!("{name:<width$}{value:>8}");
+
+    let (words, settings, times) = (&stats.words, &stats.settings, &stats.times);
+    let rows = [
+        row("Scenes", stats.scenes),
+        row("  Interior", settings.interior),
+        row("  Exterior", settings.exterior),
+        row("  Int./Ext.", settings.both),
+        row("  Other", settings.other),
+        row("  Day", times.day),
+        row("  Night", times.night),
+        row("  Other", times.other),
+        String::new(),
+        row("Words", words.total()),
+        row("  Headings", words.headings),
+        row("  Action", words.action),
+        row("  Dialogue", words.dialogue),
+        row("  Parentheticals", words.parentheticals),
+        row("  Lyrics", words.lyrics),
+        row("  Transitions", words.transitions),
+        row("  Centered", words.centered),
+        row("  Sections", words.sections),
+        row("  Synopses", words.synopses),
+    ];
+    for row in rows {
+        writeln!(writer, "{}", row.trim_end())?;
+    }
+
+    writeln!(
+        writer,
+        "\n{:<width$}{:>8}{:>8}",
+        "Character", "Lines", "Words"
+    )?;
+    for character in &stats.characters {
+        writeln!(
+            writer,
+            "{:<width$}{:>8}{:>8}",
+            character.name, character.lines, character.words
+        )?;
+    }
+
+    writeln!(
+        writer,
+        "\n{:<width$}{:>8}{:>8}",
+        "Longest speech", "Words", "Scene"
+    )?;
+    for speech in &stats.longest_speeches {
+        // Scenes are numbered from 1 here, as they are counted by people
+        let scene = speech
+            .scene
+            .map_or("-".to_string(), |i| (i + 1).to_string());
+        writeln!(
+            writer,
+            "{:<width$}{:>8}{:>8}",
+            speech.character, speech.words, scene
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes the page count and the length of each scene in eighths of a page, as aligned columns
+/// of plain text.
+fn write_layout_table(
+    mut writer: impl Write,
+    screenplay: &Screenplay,
+    layout: &Layout,
+) -> Result<()> {
+    writeln!(writer, "\n{:<18}{:>8}", "Pages", layout.pages)?;
+
+    writeln!(
+        writer,
+        "\n{:<8}{:>6}{:>8}  Heading",
+        "Scene", "Page", "Length"
+    )?;
+    let scenes = screenplay.scenes();
+    for ((scene, heading), eighths) in scenes.iter().zip(&layout.headings).zip(layout.eighths()) {
+        let number = scene
+            .number
+            .map_or((scene.index + 1).to_string(), str::to_string);
+        writeln!(
+            writer,
+            "{number:<8}{:>6}{:>8}  {}",
+            heading.position.page,
+            rustwell::format_eighths(eighths),
+            scene.heading.plain_text()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes all transitions in upper case without surrounding whitespace, so that most of them
+/// no longer need to be forced. Notes and boneyards are kept as they are.
+fn tidy_transitions(screenplay: &mut Screenplay) {
+    let is_text = |e: &rustwell::rich_string::Element| !e.is_note() && !e.is_boneyard();
+    for element in &mut screenplay.elements {
+        if let Element::Transition(s) = &mut element.node {
+            for e in s.elements.iter_mut().filter(|e| is_text(e)) {
+                e.text = e.text.to_uppercase();
+            }
+            if let Some(e) = s.elements.iter_mut().find(|e| is_text(e)) {
+                e.text = e.text.trim_start().to_string();
+            }
+            if let Some(e) = s.elements.iter_mut().rfind(|e| is_text(e)) {
+                e.text = e.text.trim_end().to_string();
+            }
+            s.elements.retain(|e| !is_text(e) || !e.text.is_empty());
+        }
+    }
+}
+
+/// The first line, counted from 1, where `src` and `formatted` differ.
+fn first_difference(src: &str, formatted: &str) -> Option<usize> {
+    if src == formatted {
+        return None;
+    }
+
+    let same = src
+        .lines()
+        .zip(formatted.lines())
+        .take_while(|(a, b)| a == b)
+        .count();
+    Some(same + 1)
+}
+
+/// Reads a whole source file, or stdin for `-`.
+fn read_source(path: &str) -> Result<String> {
+    let mut src = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut src)?;
+    } else {
+        File::open(path)?.read_to_string(&mut src)?;
+    }
+    Ok(src)
+}
+
+/// The name of a source file in messages.
+fn source_name(path: &str) -> &str {
+    if path == "-" { "<stdin>" } else { path }
+}
+
+/// Formats a [Diagnostic] in the style of a compiler, pointing out the source line it concerns.
+fn format_diagnostic(name: &str, src: &str, diagnostic: &Diagnostic) -> String {
+    let start = diagnostic.span.start;
+    let line = src.lines().nth(start.line - 1).unwrap_or_default();
+
+    // Mark the span up to the end of its first line, with at least one marker
+    let width = if diagnostic.span.end.line == start.line {
+        diagnostic.span.end.column - start.column
+    } else {
+        line.chars().count() + 1 - start.column
+    };
+    let gutter = " ".repeat(start.line.to_string().len());
+
+    format!(
+        "{}: {}\n{gutter}--> {name}:{}:{}\n{gutter} |\n{} | {line}\n{gutter} | {}{}",
+        diagnostic.severity,
+        diagnostic.message,
+        start.line,
+        start.column,
+        start.line,
+        " ".repeat(start.column - 1),
+        "^".repeat(width.max(1)),
+    )
+}
+
 fn decide_reader(cli: &Cli) -> Result<Box<dyn Read>> {
-    if cli.src == "-" {
+    if cli.src() == "-" {
         Ok(Box::new(io::stdin()))
     } else {
-        let file = File::open(&cli.src)?;
+        let file = File::open(cli.src())?;
         Ok(Box::new(BufReader::new(file)))
     }
 }
 
-fn decide_target(cli: &Cli) -> Target {
-    cli.target
-        .or_else(|| {
-            let out_str = if cli.stdout {
-                Some("-")
-            } else {
-                cli.out.as_deref()
-            };
+fn decide_source(cli: &Cli) -> Source {
+    cli.from
+        .unwrap_or_else(|| detect_source_from_path(cli.src()))
+}
 
-            out_str.and_then(|s| detect_target_from_path(s).ok())
-        })
-        .unwrap_or(Target::Pdf)
+fn detect_source_from_path(path: &str) -> Source {
+    let is_json = Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    if is_json {
+        Source::Json
+    } else {
+        Source::Fountain
+    }
 }
 
-fn detect_target_from_path(path: &str) -> Result<Target> {
+/// All [Exporter]s the CLI can export to, the default one first.
+fn exporters() -> Vec<Box<dyn Exporter>> {
+    let mut exporters = rustwell::exporters();
+    exporters.push(Box::new(JsonExporter));
+    exporters
+}
+
+fn decide_target<'a>(cli: &Cli, exporters: &'a [Box<dyn Exporter>]) -> Result<&'a dyn Exporter> {
+    if let Some(name) = &cli.target {
+        return match exporters
+            .iter()
+            .find(|e| e.name().eq_ignore_ascii_case(name))
+        {
+            Some(exporter) => Ok(exporter.as_ref()),
+            None => {
+                let names: Vec<&str> = exporters.iter().map(|e| e.name()).collect();
+                bail!(
+                    "unknown target '{name}'; expected one of {}",
+                    names.join(", ")
+                )
+            }
+        };
+    }
+
+    let out_str = if cli.stdout {
+        Some("-")
+    } else {
+        cli.out.as_deref()
+    };
+    Ok(out_str
+        .and_then(|s| detect_target_from_path(s, exporters).ok())
+        .unwrap_or(exporters[0].as_ref()))
+}
+
+fn detect_target_from_path<'a>(
+    path: &str,
+    exporters: &'a [Box<dyn Exporter>],
+) -> Result<&'a dyn Exporter> {
     if path == "-" {
         bail!("cannot detect target from stdout ('-'); specify -t/--target")
     }
@@ -95,25 +677,90 @@ fn detect_target_from_path(path: &str) -> Result<Target> {
         .unwrap_or_default()
         .to_ascii_lowercase();
 
-    let t = match ext.as_str() {
-        "typ" => Target::Typst,
-        "html" | "htm" => Target::Html,
-        "pdf" => Target::Pdf,
-        _ => bail!("unkown extension '.{}'; specify -t/--target", ext),
+    match exporters
+        .iter()
+        .find(|e| e.extensions().contains(&ext.as_str()))
+    {
+        Some(exporter) => Ok(exporter.as_ref()),
+        None => bail!("unkown extension '.{}'; specify -t/--target", ext),
+    }
+}
+
+fn decide_options(args: &ExportArgs) -> Result<ExportOptions> {
+    let theme = match &args.css {
+        Some(path) => Theme::Css(std::fs::read_to_string(path)?),
+        None => Theme::Default,
     };
+    let mut options = ExportOptions::new()
+        .synopses(args.synopses)
+        .notes(args.notes.into())
+        .cuts(args.cuts)
+        .sections(args.sections)
+        .scene_numbers(!args.no_scene_numbers)
+        .paper(args.paper)
+        .system_fonts(args.system_fonts)
+        .theme(theme);
+    if let Some(font) = &args.font {
+        options = options.font(font);
+    }
+    for path in &args.font_paths {
+        options = options.font_path(path);
+    }
+    if let Some(path) = &args.template {
+        options = options.template(std::fs::read_to_string(path)?);
+    }
+    if !args.fallback_fonts.is_empty() {
+        options = options.fallback_fonts(&args.fallback_fonts);
+    }
+    Ok(options)
+}
+
+/// Parses a [PaperSize] from `letter`, `a4`, or a custom `<width>x<height><unit>` with the unit
+/// being `mm`, `cm`, `in` or `pt`.
+fn parse_paper(s: &str) -> std::result::Result<PaperSize, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "letter" => return Ok(PaperSize::Letter),
+        "a4" => return Ok(PaperSize::A4),
+        _ => (),
+    }
 
-    Ok(t)
+    let error = || format!("invalid paper size '{s}'; expected letter, a4 or like 148x210mm");
+    let unit = s.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.' || c == 'x');
+    let points = match unit {
+        "mm" => 72.0 / 25.4,
+        "cm" => 72.0 / 2.54,
+        "in" => 72.0,
+        "pt" => 1.0,
+        _ => return Err(error()),
+    };
+    let (width, height) = s[..s.len() - unit.len()]
+        .split_once('x')
+        .ok_or_else(error)?;
+    let length = |n: &str| match n.parse::<f64>() {
+        Ok(n) if n > 0.0 => Ok(n * points),
+        _ => Err(error()),
+    };
+    Ok(PaperSize::Custom {
+        width: length(width)?,
+        height: length(height)?,
+    })
 }
 
-fn decide_writer(cli: &Cli, target: &Target) -> Result<Box<dyn Write>> {
+fn decide_writer(cli: &Cli, exporter: &dyn Exporter) -> Result<Box<dyn Write>> {
     if cli.stdout || cli.out.as_deref().unwrap_or_default() == "-" {
         Ok(Box::new(BufWriter::new(io::stdout())))
     } else if let Some(path) = cli.out.as_deref() {
         let f = File::create(path)?;
         Ok(Box::new(BufWriter::new(f)))
     } else {
-        let path = Path::new(detect_name_from_path(&cli.src)?)
-            .with_extension(extension_from_target(target));
+        let extension = exporter.extensions().first().copied().unwrap_or_default();
+        let path = Path::new(detect_name_from_path(cli.src())?).with_extension(extension);
+        if path == Path::new(cli.src()) {
+            bail!(
+                "cannot write to the source '{}'; specify -o/--out",
+                path.display()
+            )
+        }
         let f = File::create(path)?;
         Ok(Box::new(BufWriter::new(f)))
     }
@@ -130,10 +777,130 @@ fn detect_name_from_path(path: &str) -> Result<&str> {
         .unwrap_or_default())
 }
 
-fn extension_from_target(target: &Target) -> &'static str {
-    match target {
-        Target::Typst => "typ",
-        Target::Html => "html",
-        Target::Pdf => "pdf",
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file in a new temporary directory, which is removed again when dropped.
+    struct TempFile {
+        dir: std::path::PathBuf,
+        path: String,
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Creates a file with `contents` in a new temporary directory.
+    fn temp_file(name: &str, contents: &str) -> TempFile {
+        let dir = std::env::temp_dir().join(format!("rustwell-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        TempFile {
+            path: path.to_str().unwrap().to_string(),
+            dir,
+        }
+    }
+
+    fn fmt_args(files: &[&str], check: bool) -> FmtArgs {
+        FmtArgs {
+            files: files.iter().map(|f| f.to_string()).collect(),
+            check,
+            number_scenes: false,
+            lock_scenes: false,
+            caseless_cues: false,
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn tidies_transitions() {
+        let mut screenplay = rustwell::parse("The end.\n\n>  fade out. [[slowly]]  \n\nINT. HOUSE");
+        tidy_transitions(&mut screenplay);
+
+        let Element::Transition(s) = &screenplay.elements[1].node else {
+            panic!("Should be a transition");
+        };
+        assert_eq!(s.plain_text(), "FADE OUT.");
+        assert!(s.elements.last().unwrap().is_note());
+        assert_eq!(s.elements.last().unwrap().text, "slowly");
+    }
+
+    #[test]
+    fn finds_first_difference() {
+        assert_eq!(first_difference("a\nb\n", "a\nb\n"), None);
+        assert_eq!(first_difference("a\nb\n", "a\nc\n"), Some(2));
+        assert_eq!(first_difference("a\nb", "a\nb\n"), Some(3));
+        assert_eq!(first_difference("", "a\n"), Some(1));
+    }
+
+    #[test]
+    fn fails_check_on_unformatted_files() {
+        let formatted = temp_file("formatted.fountain", "INT. HOUSE - DAY\n\nBob waits.\n");
+        let unformatted = temp_file("unformatted.fountain", "INT. HOUSE - DAY\nBob waits.\n\n\n");
+
+        assert!(fmt(&fmt_args(&[&formatted.path], true)).is_ok());
+        assert!(fmt(&fmt_args(&[&formatted.path, &unformatted.path], true)).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&unformatted.path).unwrap(),
+            "INT. HOUSE - DAY\nBob waits.\n\n\n"
+        );
+    }
+
+    #[test]
+    fn formats_remaining_files_after_failure() {
+        let missing = temp_file("missing.fountain", "");
+        let missing_path = format!("{}.missing", missing.path);
+        let unformatted = temp_file("rest.fountain", "Bob waits.\n\n\n\nAlice leaves.");
+
+        assert!(fmt(&fmt_args(&[&missing_path, &unformatted.path], false)).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&unformatted.path).unwrap(),
+            "Bob waits.\n\nAlice leaves.\n"
+        );
+    }
+
+    #[test]
+    fn exports_files_named_like_commands() {
+        let exists = |path: &str| path == "fmt";
+
+        let cli = parse_cli(&args(&["rustwell", "fmt", "-o", "fmt.pdf"]), exists).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.src(), "fmt");
+        assert_eq!(cli.out.as_deref(), Some("fmt.pdf"));
+
+        let cli = parse_cli(&args(&["rustwell", "--", "stats"]), |_| false).unwrap();
+        assert_eq!(cli.src(), "stats");
+
+        let cli = parse_cli(&args(&["rustwell", "fmt", "a.fountain"]), exists).unwrap();
+        assert!(matches!(cli.command, Some(Command::Fmt(_))));
+
+        assert!(parse_cli(&args(&["rustwell", "fmt"]), |_| false).is_err());
+    }
+
+    #[test]
+    fn shares_export_flags_with_stats() {
+        let src = [
+            "rustwell",
+            "stats",
+            "a.fountain",
+            "-p",
+            "--paper",
+            "a4",
+            "--cuts",
+        ];
+        let cli = parse_cli(&args(&src), |_| false).unwrap();
+
+        let Some(Command::Stats(stats)) = cli.command else {
+            panic!("Should be stats");
+        };
+        assert!(stats.pages && stats.export.cuts);
+        assert_eq!(decide_options(&stats.export).unwrap().paper, PaperSize::A4);
     }
 }
@@ -0,0 +1,69 @@
+//! This module implements [Diagnostic]s, which point out suspicious or malformed *Fountain*
+//! that the parser still accepted, together with where in the source it was written.
+//!
+//! # Examples
+//!
+//! ```
+//! use rustwell::{ParseOptions, parse_with_diagnostics};
+//! use rustwell::diagnostic::Severity;
+//!
+//! let (_, diagnostics) =
+//!     parse_with_diagnostics("INT. HOUSE - DAY\n\n/* Cut it all", ParseOptions::default());
+//!
+//! assert_eq!(diagnostics.len(), 1);
+//! assert_eq!(diagnostics[0].severity, Severity::Warning);
+//! assert_eq!(diagnostics[0].span.start.line, 3);
+//! ```
+
+use std::fmt;
+
+use crate::span::Span;
+
+/// How serious a [Diagnostic] is.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// The source is valid, but most likely not written as intended.
+    Warning,
+    /// The source could not be understood as intended.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A message about a part of the source, found while parsing.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Where in the original source the problem was found.
+    pub span: Span,
+}
+
+impl Diagnostic {
+    /// Creates a new [Severity::Warning] [Diagnostic].
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// Writes the [Diagnostic] as `line:column: severity: message`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.span.start.line, self.span.start.column, self.severity, self.message
+        )
+    }
+}
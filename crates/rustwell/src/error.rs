@@ -0,0 +1,160 @@
+//! This module implements the [Error] type returned by every fallible part of Rustwell.
+
+use std::{fmt, io, ops::Range, path::PathBuf, string::FromUtf8Error};
+
+use typst::diag::{Severity, SourceDiagnostic};
+
+/// A [Result](std::result::Result) with Rustwell's [Error] as its default error.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Everything that can go wrong when reading *Fountain* or exporting a
+/// [Screenplay](crate::Screenplay).
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the source or writing the output failed.
+    Io(io::Error),
+    /// The source is not valid UTF-8.
+    InvalidUtf8(FromUtf8Error),
+    /// The byte range of an [Edit](crate::incremental::Edit) is out of bounds or not on [char]
+    /// boundaries in the source it is applied to.
+    Edit(Range<usize>),
+    /// The generated [typst] document could not be compiled, with the diagnostics from [typst].
+    Compile(Vec<SourceDiagnostic>),
+    /// The compiled [typst] document could not be exported as a `pdf`, with the diagnostics
+    /// from [typst_pdf].
+    Pdf(Vec<SourceDiagnostic>),
+    /// A font file, or a file in a font directory, could not be read as a font.
+    Font(PathBuf),
+    /// No font of the chosen family was bundled with Rustwell or loaded from the font paths.
+    UnknownFont(String),
+    /// A custom [typst] template is not valid [typst], does not define the functions Rustwell
+    /// calls, or does not take the arguments Rustwell calls them with, as described by the
+    /// message.
+    Template(String),
+    /// The width or height, in points, of a custom [PaperSize](crate::PaperSize) is not
+    /// positive and finite.
+    Paper { width: f64, height: f64 },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {err}"),
+            Error::InvalidUtf8(err) => write!(f, "source is not valid UTF-8: {err}"),
+            Error::Edit(range) => write!(
+                f,
+                "edit of {}..{} is out of bounds or not on character boundaries",
+                range.start, range.end
+            ),
+            Error::Compile(diagnostics) => {
+                write!(f, "failed to compile typst document")?;
+                fmt_diagnostics(f, diagnostics)
+            }
+            Error::Pdf(diagnostics) => {
+                write!(f, "failed to generate pdf")?;
+                fmt_diagnostics(f, diagnostics)
+            }
+            Error::Font(path) => write!(f, "'{}' is not a font file", path.display()),
+            Error::UnknownFont(family) => write!(f, "unknown font family '{family}'"),
+            Error::Template(message) => write!(f, "invalid template: {message}"),
+            Error::Paper { width, height } => {
+                write!(f, "invalid paper size of {width}pt by {height}pt")
+            }
+        }
+    }
+}
+
+/// Writes each [typst] diagnostic on a line of its own.
+fn fmt_diagnostics(f: &mut fmt::Formatter<'_>, diagnostics: &[SourceDiagnostic]) -> fmt::Result {
+    for diagnostic in diagnostics {
+        let severity = match diagnostic.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "\n{severity}: {}", diagnostic.message)?;
+    }
+    Ok(())
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::InvalidUtf8(err) => Some(err),
+            Error::Edit(_)
+            | Error::Compile(_)
+            | Error::Pdf(_)
+            | Error::Font(_)
+            | Error::UnknownFont(_)
+            | Error::Template(_)
+            | Error::Paper { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(err: FromUtf8Error) -> Self {
+        Error::InvalidUtf8(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use typst::{diag::SourceDiagnostic, syntax::Span};
+
+    use super::*;
+
+    #[test]
+    fn displays_errors() {
+        let io = Error::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        assert_eq!(io.to_string(), "I/O error: no such file");
+
+        let diagnostics = vec![
+            SourceDiagnostic::error(Span::detached(), "unknown variable"),
+            SourceDiagnostic::warning(Span::detached(), "unused import"),
+        ];
+        assert_eq!(
+            Error::Compile(diagnostics).to_string(),
+            "failed to compile typst document\nerror: unknown variable\nwarning: unused import"
+        );
+        assert_eq!(
+            Error::Font("Cargo.toml".into()).to_string(),
+            "'Cargo.toml' is not a font file"
+        );
+        assert_eq!(
+            Error::Paper {
+                width: 0.0,
+                height: 792.0
+            }
+            .to_string(),
+            "invalid paper size of 0pt by 792pt"
+        );
+    }
+
+    #[test]
+    fn converts_and_keeps_sources() {
+        let utf8 = String::from_utf8(vec![0xff]).unwrap_err();
+        let invalid = Error::from(utf8);
+        assert!(matches!(invalid, Error::InvalidUtf8(_)));
+        assert!(
+            invalid
+                .to_string()
+                .starts_with("source is not valid UTF-8: ")
+        );
+        assert!(invalid.source().is_some());
+
+        let io = Error::from(io::Error::other("broken pipe"));
+        assert!(matches!(&io, Error::Io(err) if err.to_string() == "broken pipe"));
+        assert!(io.source().is_some());
+
+        assert!(Error::UnknownFont("Arial".into()).source().is_none());
+    }
+}
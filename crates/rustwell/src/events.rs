@@ -0,0 +1,527 @@
+//! This module implements a pull-based parser, which reads *Fountain* from a [BufRead] and
+//! yields the [Screenplay](crate::Screenplay) as a stream of [Event]s, one block at a time.
+//!
+//! Only a single block of the source, meaning the lines up to the next blank line, is kept in
+//! memory at a time, so that even huge scripts can be converted in constant memory. The events
+//! and their [Span]s are the same as when parsing the whole source with
+//! [parse_with_options](crate::parse_with_options), except for boneyards longer than 64 KiB.
+//! Those are split into several blocks, each yielding an [Event::Boneyard] of its own, so that
+//! an unterminated boneyard does not read the rest of the source into memory.
+//!
+//! The [Diagnostic]s found along the way are collected, and taken with
+//! [Events::take_diagnostics].
+//!
+//! # Examples
+//!
+//! ```
+//! use rustwell::events::{Event, Events};
+//!
+//! let src = "INT. HOUSE - DAY\n\nBOB\n(quietly)\nHello?\n";
+//! let events = Events::new(src.as_bytes())
+//!     .map(|e| e.map(|e| e.node))
+//!     .collect::<rustwell::Result<Vec<Event>>>()
+//!     .unwrap();
+//!
+//! assert!(matches!(events[0], Event::Heading { .. }));
+//! assert!(matches!(events[1], Event::StartDialogue { .. }));
+//! assert_eq!(events[2], Event::Parenthetical("(quietly)".into()));
+//! assert_eq!(events[3], Event::Text("Hello?".into()));
+//! assert_eq!(events[4], Event::EndDialogue);
+//! ```
+
+use std::{collections::VecDeque, io::BufRead};
+
+use crate::{
+    diagnostic::Diagnostic,
+    error::Result,
+    parser::{self, ParseOptions, UNTERMINATED_BONEYARD},
+    rich_string::RichString,
+    screenplay::{Dialogue, DialogueElement, Element, TitlePage},
+    span::{Position, Span, Spanned},
+};
+
+/// A part of a [Screenplay](crate::Screenplay), as yielded by [Events].
+///
+/// Dialogue is split up into a start and an end event, with its lines and parentheticals in
+/// between. Dual dialogue is in turn made up of two dialogues between its start and end events.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Event {
+    /// The title page, only ever yielded as the first event.
+    TitlePage(TitlePage),
+    /// A section (`# Act`), see [Element::Section].
+    Section {
+        depth: usize,
+        title: RichString,
+    },
+    Heading {
+        slug: RichString,
+        number: Option<String>,
+    },
+    Action(RichString),
+    StartDialogue {
+        character: RichString,
+        extension: Option<RichString>,
+    },
+    Parenthetical(RichString),
+    /// A line of dialogue.
+    Text(RichString),
+    EndDialogue,
+    StartDualDialogue,
+    EndDualDialogue,
+    Lyrics(RichString),
+    Transition(RichString),
+    CenteredText(RichString),
+    Synopsis(RichString),
+    /// A note standing on its own, see [Element::Note].
+    Note(RichString),
+    /// Cut material standing on its own, see [Element::Boneyard].
+    Boneyard(String),
+    PageBreak,
+}
+
+/// The most bytes read into a block ending within a boneyard, after which the boneyard is split
+/// into the next block.
+const MAX_BONEYARD_BLOCK: usize = 64 * 1024;
+
+/// An [Iterator] of the [Event]s of a *Fountain* source, read from a [BufRead].
+///
+/// Every [Event] comes with the [Span] it was parsed from. Start and end events both span the
+/// whole dialogue. Fails with [Error::Io](crate::Error::Io) if the reader fails, and with
+/// [Error::InvalidUtf8](crate::Error::InvalidUtf8) if a line of the source is not valid UTF-8,
+/// after which no more events are yielded.
+pub struct Events<R> {
+    reader: R,
+    options: ParseOptions,
+    /// Where in the source the next block starts.
+    start: Position,
+    blocks: Blocks,
+    /// A line that has been read, but belongs to the next block.
+    next_line: Option<Vec<u8>>,
+    /// The last dialogue parsed, held back until it is known whether the next block turns it
+    /// into dual dialogue.
+    previous: Option<Spanned<Element>>,
+    /// Whether the last block read ends within a boneyard, which the next block then starts
+    /// within.
+    split: bool,
+    /// The warning for a boneyard split across blocks, held back until it is known whether a
+    /// later block closes it.
+    unterminated: Option<Diagnostic>,
+    events: VecDeque<Spanned<Event>>,
+    diagnostics: Vec<Diagnostic>,
+    done: bool,
+}
+
+impl<R: BufRead> Events<R> {
+    /// Creates a new [Events] reading from `reader`, with the default [ParseOptions].
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, ParseOptions::default())
+    }
+
+    /// Creates a new [Events] reading from `reader`, with [ParseOptions] deciding what is kept
+    /// of the source.
+    pub fn with_options(reader: R, options: ParseOptions) -> Self {
+        Self {
+            reader,
+            options,
+            start: Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+            blocks: Blocks::default(),
+            next_line: None,
+            previous: None,
+            split: false,
+            unterminated: None,
+            events: VecDeque::new(),
+            diagnostics: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Takes the [Diagnostic]s found in the blocks parsed so far, ordered by where they appear
+    /// in the source. Blocks are parsed as their events are yielded, and the diagnostics of a
+    /// block are kept until they are taken.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Reads the next block of the source, including the blank lines after it, and whether it
+    /// starts within a boneyard. A block never ends within a boneyard or a note, so that it can
+    /// be parsed on its own, unless the boneyard is longer than [MAX_BONEYARD_BLOCK].
+    fn read_block(&mut self) -> Result<Option<(String, bool)>> {
+        let within_boneyard = std::mem::take(&mut self.split);
+        let mut block = self.next_line.take().unwrap_or_default();
+
+        loop {
+            let mut line = Vec::new();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+
+            let starts_block = self.blocks.starts_block(&line);
+            if !self.blocks.in_boneyard {
+                self.unterminated = None;
+            }
+            if starts_block {
+                self.next_line = Some(line);
+                break;
+            }
+            block.extend(line);
+
+            if self.blocks.in_boneyard && block.len() > MAX_BONEYARD_BLOCK {
+                self.split = true;
+                break;
+            }
+        }
+
+        if block.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((String::from_utf8(block)?, within_boneyard)))
+    }
+
+    /// Parses the next block of the source into events.
+    fn parse_block(&mut self, block: &str, within_boneyard: bool) {
+        let (screenplay, mut diagnostics) = parser::parse_part(
+            block,
+            self.options,
+            self.start,
+            self.previous.take(),
+            within_boneyard,
+        );
+        if self.split {
+            // Only a later block can tell if the boneyard is closed
+            let unterminated = diagnostics
+                .iter()
+                .position(|d| d.message == UNTERMINATED_BONEYARD);
+            if let Some(i) = unterminated {
+                self.unterminated = Some(diagnostics.remove(i));
+            }
+        } else {
+            // A split boneyard still open after a block that is not split is never closed
+            diagnostics.extend(self.unterminated.take());
+            diagnostics.sort_by_key(|d| d.span.start);
+        }
+        self.diagnostics.extend(diagnostics);
+
+        let lines = block.matches('\n').count();
+        self.start = Position {
+            offset: self.start.offset + block.len(),
+            line: self.start.line + lines,
+            column: 1,
+        };
+
+        if let Some(titlepage) = screenplay.titlepage {
+            self.events.push_back(Spanned::new(
+                Event::TitlePage(titlepage.node),
+                titlepage.span,
+            ));
+        }
+
+        let mut elements = screenplay.elements;
+        if let Some(Element::Dialogue(_)) = elements.last().map(|e| &e.node) {
+            self.previous = elements.pop();
+        }
+        for element in elements {
+            push_events(element, &mut self.events);
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Events<R> {
+    type Item = Result<Spanned<Event>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.events.is_empty() && !self.done {
+            match self.read_block() {
+                Ok(Some((block, within_boneyard))) => self.parse_block(&block, within_boneyard),
+                Ok(None) => {
+                    self.done = true;
+                    self.diagnostics.extend(self.unterminated.take());
+                    if let Some(previous) = self.previous.take() {
+                        push_events(previous, &mut self.events);
+                    }
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        self.events.pop_front().map(Ok)
+    }
+}
+
+/// Finds where the blocks of a source start, fed one line at a time.
+///
+/// A block is made up of the lines up to the next blank line, together with the blank lines
+/// after it. A block never ends within a boneyard or a note, so that it can be parsed on its own.
+#[derive(Debug, Default)]
+pub(crate) struct Blocks {
+    in_boneyard: bool,
+    in_note: bool,
+    in_blank_lines: bool,
+}
+
+impl Blocks {
+    /// Feeds the next line of the source, including its newline, returning if it starts a new
+    /// block.
+    pub(crate) fn starts_block(&mut self, line: &[u8]) -> bool {
+        // A note that is not closed ends at the first empty line
+        if self.in_note && line == b"\n" {
+            self.in_note = false;
+        }
+        let blank = !self.in_boneyard && !self.in_note && is_blank(line);
+        let starts_block = self.in_blank_lines && !blank;
+
+        self.in_blank_lines = blank;
+        (self.in_boneyard, self.in_note) = scan_trivia(line, self.in_boneyard, self.in_note);
+        starts_block
+    }
+
+    /// The offsets of where all blocks of `src` start, beginning with `0`.
+    pub(crate) fn starts(src: &str) -> Vec<usize> {
+        let mut blocks = Blocks::default();
+        let mut starts = vec![0];
+        let mut offset = 0;
+        for line in src.split_inclusive('\n') {
+            if blocks.starts_block(line.as_bytes()) {
+                starts.push(offset);
+            }
+            offset += line.len();
+        }
+        starts
+    }
+}
+
+/// If the line is blank, in the sense that it ends a block. Lines of only whitespace starting
+/// with two spaces, or a tab, are intentionally empty lines within a block.
+fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(u8::is_ascii_whitespace) && !line.starts_with(b"  ") && !line.contains(&b'\t')
+}
+
+/// Finds where boneyards and notes start and end on a line, returning if the line ends within
+/// a boneyard and a note respectively.
+fn scan_trivia(line: &[u8], mut in_boneyard: bool, mut in_note: bool) -> (bool, bool) {
+    let mut i = 0;
+    while i + 1 < line.len() {
+        match (in_boneyard, in_note, &line[i..i + 2]) {
+            (true, _, b"*/") => in_boneyard = false,
+            (false, _, b"/*") => in_boneyard = true,
+            (false, true, b"]]") => in_note = false,
+            (false, false, b"[[") => in_note = true,
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+    (in_boneyard, in_note)
+}
+
+/// Turns an [Element] into [Event]s, pushing them onto `events`.
+fn push_events(element: Spanned<Element>, events: &mut VecDeque<Spanned<Event>>) {
+    let Spanned { node, span } = element;
+    let event = match node {
+        Element::Section { depth, title } => Event::Section { depth, title },
+        Element::Heading { slug, number } => Event::Heading { slug, number },
+        Element::Action(s) => Event::Action(s),
+        Element::Dialogue(dialogue) => {
+            push_dialogue_events(dialogue, span, events);
+            return;
+        }
+        Element::DualDialogue(dialogue1, dialogue2) => {
+            events.push_back(Spanned::new(Event::StartDualDialogue, span));
+            push_dialogue_events(dialogue1.node, dialogue1.span, events);
+            push_dialogue_events(dialogue2.node, dialogue2.span, events);
+            Event::EndDualDialogue
+        }
+        Element::Lyrics(s) => Event::Lyrics(s),
+        Element::Transition(s) => Event::Transition(s),
+        Element::CenteredText(s) => Event::CenteredText(s),
+        Element::Synopsis(s) => Event::Synopsis(s),
+        Element::Note(s) => Event::Note(s),
+        Element::Boneyard(s) => Event::Boneyard(s),
+        Element::PageBreak => Event::PageBreak,
+    };
+    events.push_back(Spanned::new(event, span));
+}
+
+/// Turns a [Dialogue] into [Event]s, pushing them onto `events`.
+fn push_dialogue_events(dialogue: Dialogue, span: Span, events: &mut VecDeque<Spanned<Event>>) {
+    let start = Event::StartDialogue {
+        character: dialogue.character,
+        extension: dialogue.extension,
+    };
+    events.push_back(Spanned::new(start, span));
+
+    for element in dialogue.elements {
+        let event = match element.node {
+            DialogueElement::Parenthetical(s) => Event::Parenthetical(s),
+            DialogueElement::Line(s) => Event::Text(s),
+        };
+        events.push_back(Spanned::new(event, element.span));
+    }
+
+    events.push_back(Spanned::new(Event::EndDialogue, span));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(src: &str, options: ParseOptions) -> Vec<Spanned<Event>> {
+        Events::with_options(src.as_bytes(), options)
+            .collect::<Result<_>>()
+            .expect("Reading from a slice never fails")
+    }
+
+    /// Collects the [Event]s of the whole source parsed at once, to compare with.
+    fn expected(src: &str, options: ParseOptions) -> Vec<Spanned<Event>> {
+        let screenplay = parser::parse_with_options(src, options);
+        let mut events = VecDeque::new();
+        if let Some(titlepage) = screenplay.titlepage {
+            events.push_back(Spanned::new(
+                Event::TitlePage(titlepage.node),
+                titlepage.span,
+            ));
+        }
+        for element in screenplay.elements {
+            push_events(element, &mut events);
+        }
+        events.into()
+    }
+
+    fn spans(events: &[Spanned<Event>]) -> Vec<Span> {
+        events.iter().map(|e| e.span).collect()
+    }
+
+    #[test]
+    fn yields_dialogue_events() {
+        let src = "BOB (V.O.)\n(quietly)\nHello?\nAnyone?";
+        let events = events(src, ParseOptions::default());
+
+        assert_eq!(
+            events,
+            vec![
+                Event::StartDialogue {
+                    character: "BOB".into(),
+                    extension: Some("V.O.".into()),
+                }
+                .into(),
+                Event::Parenthetical("(quietly)".into()).into(),
+                Event::Text("Hello?".into()).into(),
+                Event::Text("Anyone?".into()).into(),
+                Event::EndDialogue.into(),
+            ]
+        );
+        assert_eq!(events[0].span.range(), 0..src.len());
+        assert_eq!(events[3].span.start.line, 4);
+    }
+
+    #[test]
+    fn yields_dual_dialogue_across_blocks() {
+        let src = "BOB\nHello?\n\nALICE ^\nHi.\n\nThey stare.";
+        let events = events(src, ParseOptions::default());
+
+        assert_eq!(events[0].node, Event::StartDualDialogue);
+        assert_eq!(events[7].node, Event::EndDualDialogue);
+        assert_eq!(events[8].node, Event::Action("They stare.".into()));
+        assert_eq!(events, expected(src, ParseOptions::default()));
+        assert_eq!(
+            spans(&events),
+            spans(&expected(src, ParseOptions::default()))
+        );
+    }
+
+    #[test]
+    fn matches_parsing_the_whole_source() {
+        let src = "Title: Example\nAuthor: Someone\n\n# Act One\n\nINT. HOUSE - DAY\n\n\
+                   The door is [[a note]] open.\n\n\tIndented\n  \nStill the same block.\n\n\
+                   BOB\nHi.\n\nTitle: Not a title page\n\n> THE END <";
+        let events = events(src, ParseOptions::default());
+
+        assert!(matches!(events[0].node, Event::TitlePage(_)));
+        assert_eq!(events, expected(src, ParseOptions::default()));
+        assert_eq!(
+            spans(&events),
+            spans(&expected(src, ParseOptions::default()))
+        );
+    }
+
+    #[test]
+    fn keeps_boneyards_and_notes_spanning_blank_lines_together() {
+        let src = "INT. HOUSE\n\n/* This is cut\n\nand so is this */\n\n[[A note\n  \nwith a gap]]";
+        let options = ParseOptions {
+            boneyards: true,
+            ..Default::default()
+        };
+        let events = events(src, options);
+
+        assert_eq!(
+            events[1].node,
+            Event::Boneyard(" This is cut\n\nand so is this ".to_string())
+        );
+        assert_eq!(events[2].node, Event::Note("A note with a gap".into()));
+        assert_eq!(events, expected(src, options));
+        assert_eq!(spans(&events), spans(&expected(src, options)));
+    }
+
+    #[test]
+    fn collects_diagnostics() {
+        let src = "INT. HOUSE\n\nThe door is [[open.\n\n/* Cut it all";
+        let mut events = Events::new(src.as_bytes());
+        assert!(events.by_ref().all(|e| e.is_ok()));
+
+        let (_, expected) = parser::parse_with_diagnostics(src, ParseOptions::default());
+        assert_eq!(expected.len(), 2);
+        assert_eq!(events.take_diagnostics(), expected);
+        assert!(events.take_diagnostics().is_empty());
+    }
+
+    #[test]
+    fn splits_long_boneyards() {
+        let cut = "Cut, with a blank line after it.\n\n".repeat(5000);
+        let options = ParseOptions {
+            boneyards: true,
+            ..Default::default()
+        };
+
+        let unterminated = format!("INT. HOUSE\n\n/*{cut}");
+        let mut events = Events::with_options(unterminated.as_bytes(), options);
+        let boneyards: Vec<String> = events
+            .by_ref()
+            .filter_map(|e| match e.unwrap().node {
+                Event::Boneyard(text) => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert!(boneyards.len() > 1);
+        assert!(boneyards.iter().all(|b| b.len() <= MAX_BONEYARD_BLOCK + 64));
+        assert_eq!(boneyards.concat(), cut);
+        let diagnostics = events.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, UNTERMINATED_BONEYARD);
+        assert_eq!(diagnostics[0].span.start.line, 3);
+
+        let closed = format!("INT. HOUSE\n\n/*{cut}*/\n\nThey leave.");
+        let mut events = Events::new(closed.as_bytes());
+        let last = events.by_ref().last().unwrap().unwrap();
+        assert_eq!(last.node, Event::Action("They leave.".into()));
+        assert_eq!(last.span.start.line, 5 + 2 * 5000);
+        assert!(events.take_diagnostics().is_empty());
+    }
+
+    #[test]
+    fn fails_on_invalid_utf8() {
+        let src: &[u8] = b"INT. HOUSE\n\nThe door is \xff open.";
+        let mut events = Events::new(src);
+
+        assert!(events.next().is_some_and(|e| e.is_ok()));
+        assert!(events.next().is_some_and(|e| e.is_err()));
+        assert!(events.next().is_none());
+    }
+}
@@ -1,7 +1,324 @@
+use std::{io::Write, path::PathBuf};
+
+use crate::{error::Result, screenplay::Screenplay};
+
+pub mod fountain;
 pub mod html;
+pub mod layout;
 pub mod pdf;
 pub mod typst;
 
-pub use html::export_html;
-pub use pdf::export_pdf;
-pub use typst::export_typst;
+pub use fountain::{FountainExporter, export_fountain};
+pub use html::{HtmlExporter, export_html};
+pub use pdf::{PdfExporter, export_pdf};
+pub use typst::{TypstExporter, export_typst, missing_glyphs};
+
+/// How notes (`[[note]]`) are rendered by the exporters.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum NoteStyle {
+    /// Notes are left out of the document.
+    #[default]
+    Hidden,
+    /// Notes are highlighted where they were written.
+    Highlighted,
+    /// Notes are written as comments in the margin, next to where they were written.
+    Margin,
+    /// Notes are numbered where they were written, and written out as footnotes.
+    Footnotes,
+}
+
+/// The size of the pages of printed documents.
+///
+/// Letter and A4 pages have the same area of text, so that a page still runs about a minute of
+/// screen time. Custom pages scale the margins and indentation of Letter pages to their size,
+/// but not the 12pt text, so that they fit more or less text than a Letter page and no longer
+/// run a minute each.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum PaperSize {
+    /// US Letter, 8.5 by 11 inches, as used for screenplays in the US.
+    #[default]
+    Letter,
+    /// A4, 210 by 297 millimeters, as used for screenplays in most of the rest of the world.
+    A4,
+    /// A custom size, with the width and height in points (1/72 of an inch). Both have to be
+    /// positive and finite, or printing fails with [Error::Paper](crate::Error::Paper).
+    Custom { width: f64, height: f64 },
+}
+
+/// The font families tried, in order, for glyphs that neither the chosen font nor Courier Prime
+/// have, like those of Cyrillic, Greek, Japanese, Chinese, Korean, Hebrew or Arabic scripts.
+/// Monospaced fonts come first, so that text keeps its width where possible.
+///
+/// None of them are bundled with Rustwell, so by default none is found and those glyphs are
+/// still left blank. They are only used once loaded from [ExportOptions::font_paths], or found
+/// among the installed fonts with [ExportOptions::system_fonts].
+pub const FALLBACK_FONTS: [&str; 14] = [
+    "DejaVu Sans Mono",
+    "Liberation Mono",
+    "Noto Sans Mono",
+    "Noto Sans Mono CJK JP",
+    "Noto Sans CJK JP",
+    "Noto Sans CJK KR",
+    "Noto Sans CJK SC",
+    "Noto Sans CJK TC",
+    "Noto Sans Hebrew",
+    "Noto Sans Arabic",
+    "MS Gothic",
+    "Malgun Gothic",
+    "Hiragino Sans",
+    "Arial Unicode MS",
+];
+
+/// How `html` documents are styled.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub enum Theme {
+    /// The `css` bundled with Rustwell, laying out the document like a printed screenplay.
+    #[default]
+    Default,
+    /// No styling at all, for documents styled by the page they are embedded in.
+    Plain,
+    /// Custom `css` rules, included in the document instead of the bundled ones.
+    Css(String),
+}
+
+/// The options shared by all [Exporter]s, deciding what is included in the exported document and
+/// how it looks. Options that do not apply to a format are ignored by its [Exporter].
+///
+/// New options may be added at any time, so [ExportOptions] are built from
+/// [ExportOptions::new] or [ExportOptions::default].
+///
+/// # Examples
+///
+/// ```
+/// use rustwell::{ExportOptions, NoteStyle, PaperSize};
+///
+/// let options = ExportOptions::new()
+///     .synopses(true)
+///     .notes(NoteStyle::Margin)
+///     .paper(PaperSize::A4);
+///
+/// assert!(options.synopses && options.scene_numbers);
+/// assert_eq!(options.notes, NoteStyle::Margin);
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
+pub struct ExportOptions {
+    /// Whether synopses are included.
+    pub synopses: bool,
+    /// How notes are rendered.
+    pub notes: NoteStyle,
+    /// Whether cut material (boneyards) is shown greyed out.
+    pub cuts: bool,
+    /// Whether the titles of sections are included.
+    pub sections: bool,
+    /// Whether scene numbers are written next to the scene headings that have one.
+    pub scene_numbers: bool,
+    /// The size of the pages of printed documents.
+    pub paper: PaperSize,
+    /// The font family of printed documents, which defaults to the bundled Courier Prime.
+    /// Courier Prime is still used for glyphs missing in the chosen font.
+    pub font: Option<String>,
+    /// Font files (`ttf`, `otf`, `ttc` or `otc`), or directories of them, to load for printed
+    /// documents in addition to the bundled Courier Prime.
+    pub font_paths: Vec<PathBuf>,
+    /// The font families tried, in order, for glyphs missing in both the chosen font and
+    /// Courier Prime. Defaults to [FALLBACK_FONTS]. Only fonts loaded from the font paths, or
+    /// installed ones with `system_fonts`, can be found.
+    pub fallback_fonts: Vec<String>,
+    /// Whether the fonts installed on the system are searched for the chosen font and the
+    /// fallback fonts. Only fonts of those families are kept, but every installed font has to
+    /// be read, which makes printing slower.
+    pub system_fonts: bool,
+    /// The `typst` source of a template replacing the bundled one for printed documents, for
+    /// house styles with their own title pages, headers or scene headings. It has to define
+    /// the functions Rustwell calls, taking the same arguments as those of the bundled
+    /// template: `screenplay`, `scene`, `dialogue`, `dual_dialogue`, `lyrics`, `transition`,
+    /// `centered`, `synopsis`, `parenthetical`, and `section` when sections are included.
+    pub template: Option<String>,
+    /// How `html` documents are styled.
+    pub theme: Theme,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            synopses: false,
+            notes: NoteStyle::default(),
+            cuts: false,
+            sections: false,
+            scene_numbers: true,
+            paper: PaperSize::default(),
+            font: None,
+            font_paths: Vec::new(),
+            fallback_fonts: FALLBACK_FONTS.map(String::from).to_vec(),
+            system_fonts: false,
+            template: None,
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl ExportOptions {
+    /// The default [ExportOptions], which leave out everything that is not printed in a
+    /// screenplay, but keep scene numbers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether synopses are included.
+    pub fn synopses(mut self, synopses: bool) -> Self {
+        self.synopses = synopses;
+        self
+    }
+
+    /// Sets how notes are rendered.
+    pub fn notes(mut self, notes: NoteStyle) -> Self {
+        self.notes = notes;
+        self
+    }
+
+    /// Sets whether cut material (boneyards) is shown greyed out.
+    pub fn cuts(mut self, cuts: bool) -> Self {
+        self.cuts = cuts;
+        self
+    }
+
+    /// Sets whether the titles of sections are included.
+    pub fn sections(mut self, sections: bool) -> Self {
+        self.sections = sections;
+        self
+    }
+
+    /// Sets whether scene numbers are written next to the scene headings that have one.
+    pub fn scene_numbers(mut self, scene_numbers: bool) -> Self {
+        self.scene_numbers = scene_numbers;
+        self
+    }
+
+    /// Sets the size of the pages of printed documents.
+    pub fn paper(mut self, paper: PaperSize) -> Self {
+        self.paper = paper;
+        self
+    }
+
+    /// Sets the font family of printed documents.
+    pub fn font(mut self, family: impl Into<String>) -> Self {
+        self.font = Some(family.into());
+        self
+    }
+
+    /// Adds a font file, or a directory of font files, to load for printed documents.
+    pub fn font_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.font_paths.push(path.into());
+        self
+    }
+
+    /// Sets the font families tried, in order, for glyphs missing in both the chosen font and
+    /// Courier Prime.
+    pub fn fallback_fonts<S: Into<String>>(
+        mut self,
+        families: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.fallback_fonts = families.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether the fonts installed on the system are searched for the chosen font and the
+    /// fallback fonts.
+    pub fn system_fonts(mut self, system_fonts: bool) -> Self {
+        self.system_fonts = system_fonts;
+        self
+    }
+
+    /// Sets the `typst` source of a template replacing the bundled one for printed documents.
+    pub fn template(mut self, template: impl Into<String>) -> Self {
+        self.template = Some(template.into());
+        self
+    }
+
+    /// Sets how `html` documents are styled.
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+}
+
+/// A format a [Screenplay] can be exported to.
+///
+/// Rustwell implements an [Exporter] for each of its formats, see [exporters], and other crates
+/// can implement their own, to be used in the same way.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use rustwell::{ExportOptions, Exporter, Result, Screenplay, parse};
+///
+/// /// Writes the scene headings, one per line.
+/// struct Outline;
+///
+/// impl Exporter for Outline {
+///     fn name(&self) -> &str {
+///         "outline"
+///     }
+///
+///     fn extensions(&self) -> &[&str] {
+///         &["txt"]
+///     }
+///
+///     fn export(
+///         &self,
+///         screenplay: &Screenplay,
+///         writer: &mut dyn Write,
+///         _options: &ExportOptions,
+///     ) -> Result<()> {
+///         for scene in screenplay.scenes() {
+///             writeln!(writer, "{}", scene.heading.plain_text())?;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut out = Vec::new();
+/// let screenplay = parse("INT. HOUSE - DAY\n\nBob waits.\n\nEXT. GARDEN - NIGHT");
+/// Outline.export(&screenplay, &mut out, &ExportOptions::new())?;
+/// assert_eq!(out, b"INT. HOUSE - DAY\nEXT. GARDEN - NIGHT\n");
+/// # Ok::<(), rustwell::Error>(())
+/// ```
+pub trait Exporter {
+    /// The name of the format, like `pdf`.
+    fn name(&self) -> &str;
+
+    /// The file extensions of the format, without the leading dot and with the preferred
+    /// extension first.
+    fn extensions(&self) -> &[&str];
+
+    /// Exports the [Screenplay] and writes it to the writer.
+    fn export(
+        &self,
+        screenplay: &Screenplay,
+        writer: &mut dyn Write,
+        options: &ExportOptions,
+    ) -> Result<()>;
+
+    /// The characters that no font of the exported document has a glyph for, so that they
+    /// would be left blank, see [missing_glyphs](crate::missing_glyphs). Only formats that
+    /// render the text themselves can miss glyphs, so by default there are none.
+    fn missing_glyphs(
+        &self,
+        _screenplay: &Screenplay,
+        _options: &ExportOptions,
+    ) -> Result<Vec<char>> {
+        Ok(Vec::new())
+    }
+}
+
+/// All [Exporter]s implemented by Rustwell, for `pdf`, `html`, `typst` and *Fountain*.
+pub fn exporters() -> Vec<Box<dyn Exporter>> {
+    vec![
+        Box::new(PdfExporter),
+        Box::new(HtmlExporter),
+        Box::new(TypstExporter),
+        Box::new(FountainExporter),
+    ]
+}
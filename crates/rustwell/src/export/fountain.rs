@@ -0,0 +1,793 @@
+use std::cmp::Reverse;
+use std::io::Write;
+
+use crate::{
+    error::Result,
+    export::{ExportOptions, Exporter},
+    parser::{self, ParseOptions},
+    rich_string::{self, RichString, Style},
+    screenplay::{Dialogue, DialogueElement, Element, Screenplay, TitlePage},
+};
+
+/// Exports the [Screenplay] as *Fountain* source to the given writer, so that a [Screenplay]
+/// edited in memory can be saved again.
+///
+/// The source is written in a canonical form, with a blank line between all elements.
+/// Elements are only forced (with `!`, `@`, `.` or `>`) where they would otherwise be read as
+/// something else, including by the element before them, and `*`, `_` and `\` in the text are
+/// escaped. Boneyards are written as well, and are read back when parsing with
+/// [ParseOptions::boneyards]. Fails with [Error::Io](crate::Error::Io) if the writer fails.
+///
+/// Parsing the written source gives back the same [Screenplay], so
+/// `parse(export_fountain(s)) == s`, for everything *Fountain* can express. It cannot express:
+///
+/// - text that is empty or ends in whitespace, including line breaks, nor sections or centered
+///   text that start with whitespace, as parsing trims it,
+/// - sections of depth 0, which are written with a single `#`,
+/// - scene headings that have to be forced with `.`, but start with styled text or with neither
+///   a letter nor a digit,
+/// - transitions ending in `<`, which are read as centered text,
+/// - notes that are not a single run of words separated by single spaces, contain `]]` or `/*`,
+///   or end in `]`,
+/// - boneyards containing `*/`.
+///
+/// Such elements are written as closely as they can be, but read back differently.
+///
+/// # Examples
+///
+/// ```
+/// use rustwell::{export_fountain, parse};
+/// use rustwell::screenplay::Element;
+///
+/// let mut screenplay = parse("INT. HOUSE - DAY\n\nThe door is open.");
+/// screenplay.elements[1].node = Element::Action("5 * 3 = 15, said *someone*.".into());
+///
+/// let mut out = Vec::new();
+/// export_fountain(&screenplay, &mut out)?;
+/// let src = String::from_utf8(out)?;
+///
+/// assert_eq!(src, "INT. HOUSE - DAY\n\n5 \\* 3 = 15, said *someone*.\n");
+/// assert_eq!(parse(&src), screenplay);
+/// # Ok::<(), rustwell::Error>(())
+/// ```
+pub fn export_fountain(screenplay: &Screenplay, mut writer: impl Write) -> Result<()> {
+    let mut written: Vec<String> = Vec::new();
+    for (k, element) in screenplay.elements.iter().enumerate() {
+        let previous = k
+            .checked_sub(1)
+            .map(|p| (written[p].as_str(), &screenplay.elements[p].node));
+        written.push(export_element(element, previous));
+    }
+    let body = written.join("\n\n");
+
+    match &screenplay.titlepage {
+        Some(titlepage) if !titlepage.is_empty() => {
+            writeln!(&mut writer, "{}", export_titlepage(titlepage))?;
+        }
+        // Without a title page, a first line with a `:` would be read as one, and a blank
+        // first line would be skipped as the end of one
+        _ if body
+            .lines()
+            .next()
+            .is_some_and(|line| line.contains(':') || line.trim().is_empty()) =>
+        {
+            writeln!(&mut writer)?;
+        }
+        _ => (),
+    }
+    if !body.is_empty() {
+        writeln!(&mut writer, "{body}")?;
+    }
+    Ok(())
+}
+
+/// The [Exporter] for *Fountain* source, see [export_fountain]. The source keeps everything in
+/// the [Screenplay], so all [ExportOptions] are ignored.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FountainExporter;
+
+impl Exporter for FountainExporter {
+    fn name(&self) -> &str {
+        "fountain"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["fountain", "spmd"]
+    }
+
+    fn export(
+        &self,
+        screenplay: &Screenplay,
+        writer: &mut dyn Write,
+        _options: &ExportOptions,
+    ) -> Result<()> {
+        export_fountain(screenplay, writer)
+    }
+}
+
+/// Exports the [TitlePage] as `Key: value` lines, with the values of keys that have more than
+/// one of them indented on the lines below the key. Ends with a blank line.
+fn export_titlepage(titlepage: &TitlePage) -> String {
+    let fields = [
+        ("Title", &titlepage.title),
+        ("Credit", &titlepage.credit),
+        ("Author", &titlepage.authors),
+        ("Source", &titlepage.source),
+        ("Draft date", &titlepage.draft_date),
+        ("Contact", &titlepage.contact),
+    ];
+    let extra = titlepage
+        .extra
+        .iter()
+        .map(|(key, values)| (key.as_str(), values));
+
+    let mut out = String::new();
+    for (key, values) in fields.into_iter().chain(extra) {
+        match values.as_slice() {
+            [] => continue,
+            [value] if !value.is_empty() => {
+                out.push_str(&format!("{key}: {}\n", format_rich_string(value)));
+            }
+            values => {
+                out.push_str(&format!("{key}:\n"));
+                for value in values {
+                    out.push_str(&format!("   {}\n", format_rich_string(value)));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// The source and [Element] exported right before the one being exported, if any.
+type Previous<'a> = Option<(&'a str, &'a Element)>;
+
+/// Exports an [Element] as *Fountain*, forcing it if it would not be read back as it is after
+/// the `previous` one.
+fn export_element(element: &Element, previous: Previous) -> String {
+    match element {
+        Element::Dialogue(dialogue) => export_forced(element, previous, |forced| {
+            format_dialogue(dialogue, forced)
+        }),
+        Element::DualDialogue(dialogue1, dialogue2) => {
+            let first = Element::Dialogue(dialogue1.node.clone());
+            let first = export_forced(&first, previous, |forced| {
+                format_dialogue(dialogue1, forced)
+            });
+            export_forced(element, previous, |forced| {
+                let mut second = format_dialogue(dialogue2, forced);
+                let end = second.find('\n').unwrap_or(second.len());
+                second.insert_str(end, " ^");
+                format!("{first}\n\n{second}")
+            })
+        }
+        _ => export_forced(element, previous, |forced| format_element(element, forced)),
+    }
+}
+
+/// Exports an [Element] with `format`, only `forced` if it would not be read back otherwise.
+fn export_forced(element: &Element, previous: Previous, format: impl Fn(bool) -> String) -> String {
+    let written = format(false);
+    if reads_back(previous, &written, element) {
+        written
+    } else {
+        format(true)
+    }
+}
+
+/// If parsing `src` after the `previous` source gives back exactly the `previous` [Element]
+/// followed by `element`.
+fn reads_back(previous: Previous, src: &str, element: &Element) -> bool {
+    // The blank line first keeps the elements from being read as a title page
+    let (src, expected) = match previous {
+        Some((previous_src, previous)) => (
+            format!("\n{previous_src}\n\n{src}"),
+            vec![previous, element],
+        ),
+        None => (format!("\n{src}"), vec![element]),
+    };
+    let options = ParseOptions {
+        boneyards: true,
+        ..Default::default()
+    };
+    let parsed = parser::parse_with_options(&src, options);
+    parsed.elements.iter().map(|e| &e.node).eq(expected)
+}
+
+/// Formats an [Element] into *Fountain*, `forced` with `!`, `.` or `>` if it can be. Dialogue
+/// is always exported with [export_element] instead.
+fn format_element(element: &Element, forced: bool) -> String {
+    match element {
+        Element::Section { depth, title } => {
+            let title = escape_first(format_rich_string(title), |c| c == '#');
+            format!("{} {title}", "#".repeat((*depth).max(1)))
+        }
+        Element::Heading { slug, number } => {
+            let mut slug = format_rich_string(slug);
+            match number {
+                Some(number) => slug.push_str(&format!(" #{number}#")),
+                // Keep a `#` at the end from being read as the end of a scene number
+                None if slug.ends_with('#') => slug.insert(slug.len() - 1, '\\'),
+                None => (),
+            }
+            if forced { format!(".{slug}") } else { slug }
+        }
+        Element::Action(s) => {
+            let mut lines = format_rich_string(s)
+                .split('\n')
+                .enumerate()
+                .map(|(i, line)| {
+                    // Only centered text and lyrics are looked for within a block of action
+                    if i == 0 {
+                        return line.to_string();
+                    }
+                    let trimmed = line.trim();
+                    let centered = trimmed.starts_with('>') && trimmed.ends_with('<');
+                    if centered || trimmed.starts_with('~') {
+                        escape_first(line.to_string(), |_| true)
+                    } else {
+                        line.to_string()
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+            if forced {
+                lines.insert(0, '!');
+            }
+            lines
+        }
+        Element::Transition(s) => {
+            let s = format_rich_string(s);
+            if forced { format!(">{s}") } else { s }
+        }
+        Element::Lyrics(s) => format_lines(s, |line| format!("~{line}")),
+        Element::CenteredText(s) => format_lines(s, |line| format!("> {line} <")),
+        Element::Synopsis(s) => format_lines(s, |line| {
+            format!("={}", escape_first(line.to_string(), |c| c == '='))
+        }),
+        // Notes have no markup, so the text of every run is written, whatever its kind
+        Element::Note(s) => {
+            let text = s
+                .elements
+                .iter()
+                .map(|e| e.text.as_str())
+                .collect::<String>();
+            format!("[[{text}]]")
+        }
+        Element::Boneyard(s) => format!("/*{s}*/"),
+        Element::PageBreak => "===".to_string(),
+        Element::Dialogue(_) | Element::DualDialogue(..) => export_element(element, None),
+    }
+}
+
+/// Formats a [Dialogue] into *Fountain*, with the character `forced` with `@`. A `^` ending
+/// the line of the character is escaped, so it is not read as the caret of dual dialogue.
+fn format_dialogue(dialogue: &Dialogue, forced: bool) -> String {
+    let mut out = String::new();
+    if forced {
+        out.push('@');
+    }
+    out.push_str(&format_rich_string(&dialogue.character));
+    match &dialogue.extension {
+        Some(extension) => out.push_str(&format!(" ({})", format_rich_string(extension))),
+        None if out.ends_with('^') => out.insert(out.len() - 1, '\\'),
+        None => (),
+    }
+
+    for element in &dialogue.elements {
+        out.push('\n');
+        match &element.node {
+            DialogueElement::Parenthetical(s) => out.push_str(&format_rich_string(s)),
+            DialogueElement::Line(s) => {
+                let line = format_rich_string(s);
+                if line.is_empty() {
+                    // Two spaces keep an empty line from ending the dialogue
+                    out.push_str("  ");
+                } else {
+                    out.push_str(&escape_first(line, |c| c == '('));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Formats every line of a [RichString] with `format`.
+fn format_lines(str: &RichString, format: impl Fn(&str) -> String) -> String {
+    format_rich_string(str)
+        .split('\n')
+        .map(format)
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Escapes the first char of `s` that is not whitespace, if it matches `pattern`.
+fn escape_first(mut s: String, pattern: impl Fn(char) -> bool) -> String {
+    if let Some((i, c)) = s.char_indices().find(|(_, c)| !c.is_whitespace())
+        && pattern(c)
+    {
+        s.insert(i, '\\');
+    }
+    s
+}
+
+/// A part of a line of a [RichString].
+enum Segment<'a> {
+    Text(&'a str, Style),
+    Trivia(&'a rich_string::Element),
+}
+
+/// Formats a [RichString] into *Fountain*, with styles written as emphasis markers and notes
+/// and boneyards put back where they were.
+fn format_rich_string(str: &RichString) -> String {
+    let mut lines = vec![Vec::new()];
+    for element in &str.elements {
+        let line = lines.last_mut().expect("Always at least one line");
+        if element.is_note() || element.is_boneyard() {
+            line.push(Segment::Trivia(element));
+            continue;
+        }
+
+        for (i, part) in element.text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Vec::new());
+            }
+            if !part.is_empty() {
+                let line = lines.last_mut().expect("Always at least one line");
+                line.push(Segment::Text(part, element.style()));
+            }
+        }
+    }
+
+    lines
+        .iter()
+        .map(|segments| format_line(segments))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Formats a single line of a [RichString]. Styles are opened and closed around the texts
+/// so that they always nest, with the styles lasting the longest outermost.
+fn format_line(segments: &[Segment]) -> String {
+    let groups_at = |k: usize| {
+        segments[k..]
+            .iter()
+            .find_map(|segment| match segment {
+                Segment::Text(_, style) => Some(Group::of(*style)),
+                Segment::Trivia(_) => None,
+            })
+            .unwrap_or_default()
+    };
+    let lasts = |k: usize, group: Group| {
+        segments[k..]
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Text(_, style) => Some(Group::of(*style)),
+                Segment::Trivia(_) => None,
+            })
+            .take_while(|groups| groups.contains(&group))
+            .count()
+    };
+
+    let mut out = String::new();
+    // The groups currently open, outermost first
+    let mut open: Vec<Group> = Vec::new();
+    for (k, segment) in segments.iter().enumerate() {
+        let (text, style) = match segment {
+            Segment::Text(text, style) => (*text, *style),
+            Segment::Trivia(element) if element.is_note() => {
+                out.push_str(&format!("[[{}]]", element.text));
+                continue;
+            }
+            Segment::Trivia(element) => {
+                out.push_str(&format!("/*{}*/", element.text));
+                continue;
+            }
+        };
+
+        // Close the groups that end here, along with all groups opened within them
+        let wanted = Group::of(style);
+        let kept = open.iter().take_while(|g| wanted.contains(g)).count();
+        for closed in open.drain(kept..).rev() {
+            out.push_str(&closed.marker());
+        }
+
+        let mut opening = wanted
+            .into_iter()
+            .filter(|g| !open.contains(g))
+            .collect::<Vec<Group>>();
+        opening.sort_by_key(|g| Reverse(lasts(k, *g)));
+        for group in &opening {
+            out.push_str(&group.marker());
+        }
+        open.extend(&opening);
+
+        let closes_after = open.iter().any(|g| !groups_at(k + 1).contains(g));
+        out.push_str(&escape(text, !opening.is_empty(), closes_after));
+    }
+    for closed in open.into_iter().rev() {
+        out.push_str(&closed.marker());
+    }
+    escape_within_words(&out)
+}
+
+/// Escapes the char before every `_` marker that sits between two alphanumeric chars. Such a
+/// marker would otherwise be read as part of a word, like in `snake_case`, while a marker after
+/// an escaped char can always open or close.
+fn escape_within_words(line: &str) -> String {
+    let chars = line.chars().collect::<Vec<char>>();
+    let mut out = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' {
+            out.extend(&chars[i..(i + 2).min(chars.len())]);
+            i += 2;
+            continue;
+        }
+
+        let run = chars[i + 1..].iter().take_while(|&&c| c == '_').count();
+        if run > 0
+            && c.is_alphanumeric()
+            && chars.get(i + 1 + run).is_some_and(|c| c.is_alphanumeric())
+        {
+            out.push('\\');
+        }
+        out.push(c);
+        i += 1;
+    }
+    out
+}
+
+/// Styles that are opened and closed together. Bold and italic share the `*` marker, so they
+/// are always written as one run, like `***`, which keeps them from being matched with the
+/// wrong marker when read back.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Group {
+    /// Bold, italic or both.
+    Stars(Style),
+    Underline,
+}
+
+impl Group {
+    /// The groups of a [Style].
+    fn of(style: Style) -> Vec<Group> {
+        let mut groups = Vec::new();
+        let stars = style & (Style::BOLD | Style::ITALIC);
+        if !stars.is_empty() {
+            groups.push(Group::Stars(stars));
+        }
+        if style.contains(Style::UNDERLINE) {
+            groups.push(Group::Underline);
+        }
+        groups
+    }
+
+    /// The emphasis marker opening and closing the group.
+    fn marker(&self) -> String {
+        match self {
+            Group::Stars(style) => {
+                let bold = if style.contains(Style::BOLD) {
+                    "**"
+                } else {
+                    ""
+                };
+                let italic = if style.contains(Style::ITALIC) {
+                    "*"
+                } else {
+                    ""
+                };
+                format!("{bold}{italic}")
+            }
+            Group::Underline => "_".to_string(),
+        }
+    }
+}
+
+/// Escapes the chars of `text` that would otherwise be read as markup. Whitespace right after
+/// an opening marker, or right before a closing one, is escaped as well, as a marker is only
+/// read as such next to text.
+fn escape(text: &str, after_opening: bool, before_closing: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let first = out.is_empty();
+        let last = chars.peek().is_none();
+        let escaped = match c {
+            '*' | '_' | '\\' => true,
+            // Notes and boneyards start with `[[` and `/*`, which could be completed by what
+            // comes after the text
+            '[' => last || chars.peek() == Some(&'['),
+            '/' => last,
+            c if c.is_whitespace() => (first && after_opening) || (last && before_closing),
+            _ => false,
+        };
+        if escaped {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Spanned;
+
+    /// Exports `screenplay` as *Fountain*, checking that parsing it gives back the same
+    /// [Screenplay], and returns the source.
+    fn fountain_tester(screenplay: &Screenplay) -> String {
+        let mut out = Vec::new();
+        export_fountain(screenplay, &mut out).unwrap();
+        let src = String::from_utf8(out).unwrap();
+
+        let options = ParseOptions {
+            boneyards: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            &parser::parse_with_options(&src, options),
+            screenplay,
+            "{src}"
+        );
+        src
+    }
+
+    #[test]
+    fn round_trips_parsed_source() {
+        let src = "\
+Title: **Big** Fish
+Author:
+   John August
+   Daniel Wallace
+Series: _Fish_ Tales
+
+# Act One
+
+= Edward tells a story.
+
+INT. HOUSE - DAY #1#
+
+A snake_case name, 5 * 3 = 15 and a \\\\ or two. [[Check this.]]
+
+BOB (V.O.)
+(quietly)
+Hello, *you*.
+
+ALICE ^
+Hi!
+
+> THE END <
+
+~Lyrics in the air
+~and some more
+
+/* A cut scene */
+
+CUT TO:
+
+===
+
+[[A note on its own]]
+";
+        let screenplay = parser::parse_with_options(
+            src,
+            ParseOptions {
+                boneyards: true,
+                ..Default::default()
+            },
+        );
+        fountain_tester(&screenplay);
+    }
+
+    /// A xorshift generator, so that the same text is generated on every run.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+            items[(self.next() % items.len() as u64) as usize]
+        }
+    }
+
+    #[test]
+    fn round_trips_generated_elements() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        let chars = "ab AB*_\\[]/#~>(<=:^@.!田。".chars().collect::<Vec<char>>();
+        let styles = [
+            Style::empty(),
+            Style::BOLD,
+            Style::ITALIC,
+            Style::UNDERLINE,
+            Style::all(),
+        ];
+
+        for _ in 0..5000 {
+            let mut text = RichString::new();
+            for _ in 0..rng.next() % 4 + 1 {
+                let run = (0..rng.next() % 4 + 1).map(|_| rng.pick(&chars));
+                text.push_styled(run.collect::<String>(), rng.pick(&styles));
+            }
+            let plain = text.plain_text();
+            let raw = RichString {
+                elements: vec![rich_string::Element::new(plain.clone())],
+            };
+            let element = match rng.next() % 9 {
+                0 => Element::Action(text),
+                1 => Element::Transition(text),
+                2 => Element::CenteredText(text),
+                3 => Element::Lyrics(text),
+                4 => Element::Synopsis(text),
+                5 => Element::Heading {
+                    slug: text,
+                    number: None,
+                },
+                6 => Element::Section {
+                    depth: (rng.next() % 3 + 1) as usize,
+                    title: text,
+                },
+                7 => Element::Note(raw),
+                _ => Element::Boneyard(plain.clone()),
+            };
+
+            // Only what Fountain can express, as listed by export_fountain
+            let starts_with_space = plain.starts_with(char::is_whitespace);
+            let expressible = !plain.trim().is_empty()
+                && !plain.ends_with(char::is_whitespace)
+                && match &element {
+                    Element::Section { .. } | Element::CenteredText(_) => !starts_with_space,
+                    Element::Heading { slug, .. } => {
+                        plain.starts_with(char::is_alphanumeric)
+                            && slug.elements[0].style().is_empty()
+                    }
+                    Element::Transition(_) => !plain.ends_with('<'),
+                    Element::Note(_) => {
+                        let words = plain.split_whitespace().collect::<Vec<&str>>();
+                        words.join(" ") == plain
+                            && !plain.contains("]]")
+                            && !plain.contains("/*")
+                            && !plain.ends_with(']')
+                    }
+                    Element::Boneyard(_) => !plain.contains("*/"),
+                    _ => true,
+                };
+            if expressible {
+                fountain_tester(&Screenplay::new(None, vec![element]));
+            }
+        }
+    }
+
+    #[test]
+    fn writes_notes_of_any_run() {
+        let note = Element::Note(RichString {
+            elements: vec![rich_string::Element::note("Check this.".to_string())],
+        });
+        let mut out = Vec::new();
+        export_fountain(&Screenplay::new(None, vec![note]), &mut out).unwrap();
+        assert_eq!(out, b"[[Check this.]]\n");
+    }
+
+    #[test]
+    fn forces_misread_elements() {
+        let screenplay = Screenplay::new(
+            None,
+            vec![
+                Element::Heading {
+                    slug: "FLASHBACK".into(),
+                    number: None,
+                },
+                Element::Action("INT. LOOKS LIKE A HEADING".into()),
+                Element::Transition("Fade out.".into()),
+                Element::Dialogue(Dialogue {
+                    character: "McCoy".into(),
+                    extension: None,
+                    elements: vec![Spanned::from(DialogueElement::Line("Hi.".into()))],
+                }),
+            ],
+        );
+
+        let src = fountain_tester(&screenplay);
+        assert_eq!(
+            src,
+            ".FLASHBACK\n\n!INT. LOOKS LIKE A HEADING\n\n>Fade out.\n\n@McCoy\nHi.\n"
+        );
+    }
+
+    #[test]
+    fn escapes_literal_markup() {
+        let mut rs = RichString::new();
+        rs.push_styled("*not* _styled_ \\", Style::empty());
+        rs.push_styled(" bold ", Style::BOLD);
+        rs.push_styled("[[not a note]]/", Style::ITALIC);
+        rs.push_styled("both", Style::BOLD | Style::ITALIC);
+        let screenplay = Screenplay::new(
+            None,
+            vec![
+                Element::Action(rs),
+                Element::Action("=> ~a\n~b\n> c <".into()),
+            ],
+        );
+
+        fountain_tester(&screenplay);
+    }
+
+    #[test]
+    fn underlines_within_words() {
+        let mut rs = RichString::new();
+        rs.push_styled("snake", Style::empty());
+        rs.push_styled("case", Style::UNDERLINE);
+        rs.push_styled(" and a", Style::empty());
+        rs.push_styled("b", Style::UNDERLINE);
+        rs.push_styled("c", Style::empty());
+        let screenplay = Screenplay::new(None, vec![Element::Action(rs)]);
+
+        let src = fountain_tester(&screenplay);
+        assert_eq!(src, "snak\\e_case_ and \\a_\\b_c\n");
+    }
+
+    #[test]
+    fn writes_titlepage_block() {
+        let mut titlepage = TitlePage::new();
+        titlepage.title = vec!["Title: with a colon".into()];
+        titlepage.insert("Notes", vec!["First".into(), "Second".into()]);
+        let screenplay = Screenplay::new(Some(titlepage), vec![Element::PageBreak]);
+
+        let src = fountain_tester(&screenplay);
+        assert_eq!(
+            src,
+            "Title: Title: with a colon\nNotes:\n   First\n   Second\n\n===\n"
+        );
+    }
+
+    #[test]
+    fn keeps_colon_from_becoming_titlepage() {
+        let screenplay = Screenplay::new(None, vec![Element::Action("Time: noon".into())]);
+
+        fountain_tester(&screenplay);
+    }
+
+    #[test]
+    fn writes_dual_dialogue_caret() {
+        let dialogue = |character: &str| Dialogue {
+            character: character.into(),
+            extension: None,
+            elements: vec![Spanned::from(DialogueElement::Line("Now!".into()))],
+        };
+        let screenplay = Screenplay::new(
+            None,
+            vec![Element::DualDialogue(
+                Spanned::from(dialogue("BOB")),
+                Spanned::from(dialogue("ALICE")),
+            )],
+        );
+
+        let src = fountain_tester(&screenplay);
+        assert_eq!(src, "BOB\nNow!\n\nALICE ^\nNow!\n");
+    }
+
+    #[test]
+    fn escapes_caret_ending_character() {
+        let mut screenplay = parser::parse("BOB\nHi.\n\nALICE\nHey.");
+        let Element::Dialogue(dialogue) = &mut screenplay.elements[1].node else {
+            panic!("Should be dialogue");
+        };
+        dialogue.character = "ALICE ^".into();
+
+        let src = fountain_tester(&screenplay);
+        assert_eq!(src, "BOB\nHi.\n\nALICE \\^\nHey.\n");
+
+        let dual = parser::parse("BOB\nHi.\n\nALICE \\^ ^\nHey.");
+        assert_eq!(dual.elements.len(), 1);
+        let Element::DualDialogue(_, second) = &dual.elements[0].node else {
+            panic!("Should be dual dialogue");
+        };
+        assert_eq!(second.character, "ALICE ^".into());
+    }
+}
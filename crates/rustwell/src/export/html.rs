@@ -1,17 +1,26 @@
 use std::io::Write;
 
 use crate::{
+    error::Result,
+    export::{ExportOptions, Exporter, NoteStyle, Theme},
     rich_string::{self, RichString},
     screenplay::{Dialogue, DialogueElement, Element, Screenplay, TitlePage},
+    span::Spanned,
 };
 
 /// Contents of the `style.css` file with all css rules for the `html` output.
 const CSS: &str = include_str!("style.css");
 
 /// Exports the [Screenplay] in `html`-format to the given writer.
-/// The function allows the caller to choose to include the default `css`
-/// styling as part of the outputed file.
-pub fn export_html(screenplay: &Screenplay, mut writer: impl Write, css: bool, synopses: bool) {
+/// The [Theme] of the [ExportOptions] decides whether the default `css` styling, custom `css` or
+/// no styling at all is included in the outputed file. Fails with
+/// [Error::Io](crate::Error::Io) if the writer fails.
+pub fn export_html(
+    screenplay: &Screenplay,
+    mut writer: impl Write,
+    options: &ExportOptions,
+) -> Result<()> {
+    let (notes, cuts) = (options.notes, options.cuts);
     writeln!(
         &mut writer,
         r#"<!DOCTYPE html>
@@ -22,32 +31,34 @@ pub fn export_html(screenplay: &Screenplay, mut writer: impl Write, css: bool, s
     </head>
     <body>
         <div id="wrapper" class="screenplay">"#,
-        if css {
-            format!(r#"<style type="text/css">{}</style>"#, CSS)
-        } else {
-            "".to_string()
+        match &options.theme {
+            Theme::Default => format!(r#"<style type="text/css">{}</style>"#, CSS),
+            Theme::Plain => "".to_string(),
+            Theme::Css(css) => format!(r#"<style type="text/css">{}</style>"#, css),
         }
-    )
-    .expect("Failed to write to output");
+    )?;
     if let Some(titlepage) = &screenplay.titlepage {
-        writeln!(&mut writer, "{}", export_titlepage(titlepage))
-            .expect("Failed to write to output");
+        writeln!(&mut writer, "{}", export_titlepage(titlepage, notes, cuts))?;
     }
     for e in &screenplay.elements {
-        writeln!(&mut writer, "{}", export_element(e, synopses))
-            .expect("Failed to write to output");
+        writeln!(&mut writer, "{}", export_element(e, options))?;
+    }
+    if notes == NoteStyle::Footnotes {
+        writeln!(&mut writer, "{}", export_footnotes(screenplay, options))?;
     }
     writeln!(
         &mut writer,
         r#"</div>
     </body>
 </html>"#
-    )
-    .expect("Failed to write to output");
+    )?;
+    Ok(())
 }
 
-/// Exports the [TitlePage] to a `html` string.
-fn export_titlepage(titlepage: &TitlePage) -> String {
+/// Exports the [TitlePage] to a `html` string. Contact information is placed at the bottom
+/// left, while revisions, notes and copyright are placed at the bottom right.
+fn export_titlepage(titlepage: &TitlePage, notes: NoteStyle, cuts: bool) -> String {
+    let extra = |key: &str| titlepage.get(key).unwrap_or_default();
     format!(
         r#"
         <div id="title-page">
@@ -57,38 +68,78 @@ fn export_titlepage(titlepage: &TitlePage) -> String {
             {}
             {}
             {}
+            {}
+            <div class="bottom">
+                {}
+                <div class="bottom-right">{}{}{}</div>
+            </div>
         </div>
     "#,
-        export_titlepage_element("title", &titlepage.title),
-        export_titlepage_element("credit", &titlepage.credit),
-        export_titlepage_element("authors", &titlepage.authors),
-        export_titlepage_element("source", &titlepage.source),
-        export_titlepage_element("draft-date", &titlepage.draft_date),
-        export_titlepage_element("contact", &titlepage.contact),
+        export_titlepage_element("series", extra("Series"), notes, cuts),
+        export_titlepage_element("title", &titlepage.title, notes, cuts),
+        export_titlepage_element("episode", extra("Episode"), notes, cuts),
+        export_titlepage_element("credit", &titlepage.credit, notes, cuts),
+        export_titlepage_element("authors", &titlepage.authors, notes, cuts),
+        export_titlepage_element("source", &titlepage.source, notes, cuts),
+        export_titlepage_element("draft-date", &titlepage.draft_date, notes, cuts),
+        export_titlepage_element("contact", &titlepage.contact, notes, cuts),
+        export_titlepage_element("revision", extra("Revision"), notes, cuts),
+        export_titlepage_element("notes", extra("Notes"), notes, cuts),
+        export_titlepage_element("copyright", extra("Copyright"), notes, cuts),
     )
 }
 
 /// Exports the [TitlePage] element, meaning one of values that can be included
 /// on the [TitlePage] to a `html` string. If there are no [RichString]s we do not include
 /// the value on the [TitlePage], and only return `""` here.
-fn export_titlepage_element(value: &str, element: &[RichString]) -> String {
+fn export_titlepage_element(
+    value: &str,
+    element: &[RichString],
+    notes: NoteStyle,
+    cuts: bool,
+) -> String {
     if element.is_empty() {
         return "".to_string();
     }
 
     let content = element
         .iter()
-        .map(|s| format!("<p>{}</p>", format_rich_string(s)))
+        .map(|s| format!("<p>{}</p>", format_rich_string(s, notes, cuts)))
         .collect::<Vec<String>>()
         .concat();
 
     format!(r#"<div class="{}">{}</div>"#, value, content)
 }
 
+/// The [Exporter] for `html` documents, see [export_html].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HtmlExporter;
+
+impl Exporter for HtmlExporter {
+    fn name(&self) -> &str {
+        "html"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["html", "htm"]
+    }
+
+    fn export(
+        &self,
+        screenplay: &Screenplay,
+        writer: &mut dyn Write,
+        options: &ExportOptions,
+    ) -> Result<()> {
+        export_html(screenplay, writer, options)
+    }
+}
+
 /// Formats an [Element] into a `html`-[String].
-fn export_element(element: &Element, synopses: bool) -> String {
+fn export_element(element: &Element, options: &ExportOptions) -> String {
+    let (notes, cuts) = (options.notes, options.cuts);
     match element {
         Element::Heading { slug, number } => {
+            let number = number.as_ref().filter(|_| options.scene_numbers);
             format!(
                 r#"<h6>{}{}{}</h6>"#,
                 if let Some(x) = number {
@@ -96,7 +147,7 @@ fn export_element(element: &Element, synopses: bool) -> String {
                 } else {
                     "".to_string()
                 },
-                format_rich_string(slug),
+                format_rich_string(slug, notes, cuts),
                 if let Some(x) = number {
                     format!(r#"<span class="scnumr">{}</span>"#, x)
                 } else {
@@ -106,12 +157,12 @@ fn export_element(element: &Element, synopses: bool) -> String {
         }
         Element::Action(s) => format!(
             r#"<div class="action"><p>{}</p></div>"#,
-            format_rich_string(s)
+            format_rich_string(s, notes, cuts)
         ),
         Element::Dialogue(dialogue) => format!(
             r#"<div class="dialog"><p class="character">{}</p>{}</div>"#,
-            format_character(dialogue),
-            format_dialogue(&dialogue.elements),
+            format_character(dialogue, notes, cuts),
+            format_dialogue(&dialogue.elements, notes, cuts),
         ),
         Element::DualDialogue(dialogue1, dialogue2) => format!(
             r#"<div class="dual">
@@ -124,64 +175,107 @@ fn export_element(element: &Element, synopses: bool) -> String {
                     {}
                 </div>
             </div>"#,
-            format_character(dialogue1),
-            format_dialogue(&dialogue1.elements),
-            format_character(dialogue2),
-            format_dialogue(&dialogue2.elements),
+            format_character(dialogue1, notes, cuts),
+            format_dialogue(&dialogue1.elements, notes, cuts),
+            format_character(dialogue2, notes, cuts),
+            format_dialogue(&dialogue2.elements, notes, cuts),
         ),
         Element::Lyrics(s) => format!(
             r#"<div class="lyrics"><p>{}</p></div>"#,
-            format_rich_string(s)
+            format_rich_string(s, notes, cuts)
         ),
         Element::Transition(s) => {
-            format!(r#"<div class="transition">{}</div>"#, format_rich_string(s))
+            format!(
+                r#"<div class="transition">{}</div>"#,
+                format_rich_string(s, notes, cuts)
+            )
         }
         Element::CenteredText(s) => format!(
             r#"<div class="action centered"><p>{}</p></div>"#,
-            format_rich_string(s)
+            format_rich_string(s, notes, cuts)
         ),
         Element::Synopsis(s) => {
-            if synopses {
+            if options.synopses {
                 format!(
                     r#"<div class="synopsis"><p>{}</p></div>"#,
-                    format_rich_string(s)
+                    format_rich_string(s, notes, cuts)
+                )
+            } else {
+                "".to_string()
+            }
+        }
+        Element::Note(s) => {
+            if notes == NoteStyle::Hidden {
+                "".to_string()
+            } else {
+                format!(
+                    r#"<div class="action"><p>{}</p></div>"#,
+                    format_note(&s.plain_text(), notes)
+                )
+            }
+        }
+        Element::Boneyard(s) => {
+            if cuts {
+                format!(
+                    r#"<div class="action boneyard"><p>{}</p></div>"#,
+                    format_boneyard(s, cuts)
                 )
             } else {
                 "".to_string()
             }
         }
         Element::PageBreak => "".to_string(), // No pagebreaks in html
+        Element::Section { depth, title } => {
+            if options.sections {
+                // Sections deeper than `h5` are all styled as `h5`, as `h6` is a scene heading
+                let level = (*depth).clamp(1, 5);
+                format!(
+                    r#"<h{level}>{}</h{level}>"#,
+                    format_rich_string(title, notes, cuts)
+                )
+            } else {
+                "".to_string()
+            }
+        }
     }
 }
 
-fn format_character(dialogue: &Dialogue) -> String {
+fn format_character(dialogue: &Dialogue, notes: NoteStyle, cuts: bool) -> String {
     if let Some(extension) = &dialogue.extension {
         format!(
             "{} ({})",
-            format_rich_string(&dialogue.character),
-            format_rich_string(extension)
+            format_rich_string(&dialogue.character, notes, cuts),
+            format_rich_string(extension, notes, cuts)
         )
     } else {
-        format_rich_string(&dialogue.character)
+        format_rich_string(&dialogue.character, notes, cuts)
     }
 }
 
 /// Formats a [RichString] into a `html`-[String].
-fn format_rich_string(str: &RichString) -> String {
+fn format_rich_string(str: &RichString, notes: NoteStyle, cuts: bool) -> String {
     str.elements
         .iter()
-        .map(format_rich_element)
+        .map(|e| format_rich_element(e, notes, cuts))
         .collect::<Vec<String>>()
         .concat()
 }
 
 /// Formats a [RichString] [rich_string::Element] into a `html`-[String].
-fn format_rich_element(element: &rich_string::Element) -> String {
+fn format_rich_element(element: &rich_string::Element, notes: NoteStyle, cuts: bool) -> String {
     // Assumes newlines '\n' will only occur sole elements
     if element.text == "\n" {
         return "<br />".to_string();
     }
 
+    if element.is_note() {
+        return format_note(&element.text, notes);
+    }
+
+    if element.is_boneyard() {
+        return format_boneyard(&element.text, cuts);
+    }
+
     let prepend = format!(
         "{}{}{}",
         if element.is_bold() { "<strong>" } else { "" },
@@ -199,20 +293,179 @@ fn format_rich_element(element: &rich_string::Element) -> String {
 
 /// Formats the [Vec<DialogueElement>] of the dialogue into a `html`-[String], combining the
 /// [DialogueElement]s.
-fn format_dialogue(dialogue: &[DialogueElement]) -> String {
+fn format_dialogue(dialogue: &[Spanned<DialogueElement>], notes: NoteStyle, cuts: bool) -> String {
     dialogue
         .iter()
-        .map(format_dialogue_element)
+        .map(|e| format_dialogue_element(e, notes, cuts))
         .collect::<Vec<String>>()
         .join("\n")
 }
 
 /// Formats a [DialogueElement] into a `html`-[String].
-fn format_dialogue_element(element: &DialogueElement) -> String {
+fn format_dialogue_element(element: &DialogueElement, notes: NoteStyle, cuts: bool) -> String {
     match element {
         DialogueElement::Parenthetical(s) => {
-            format!(r#"<p class="parenthetical">{}</p>"#, format_rich_string(s))
+            format!(
+                r#"<p class="parenthetical">{}</p>"#,
+                format_rich_string(s, notes, cuts)
+            )
+        }
+        DialogueElement::Line(s) => format!(r#"<p>{}</p>"#, format_rich_string(s, notes, cuts)),
+    }
+}
+
+/// Formats the text of a note into a `html`-[String], according to the [NoteStyle].
+/// Footnotes are only marked here, and numbered through `css`, as the notes themselves are
+/// written out at the end by [export_footnotes].
+fn format_note(text: &str, notes: NoteStyle) -> String {
+    match notes {
+        NoteStyle::Hidden => "".to_string(),
+        NoteStyle::Highlighted => format!(r#"<mark class="note">{text}</mark>"#),
+        NoteStyle::Margin => format!(r#"<span class="note margin">{text}</span>"#),
+        NoteStyle::Footnotes => r#"<sup class="note-ref"></sup>"#.to_string(),
+    }
+}
+
+/// Formats the text of a boneyard into a `html`-[String], greyed out if cuts are shown.
+fn format_boneyard(text: &str, cuts: bool) -> String {
+    if cuts {
+        format!(
+            r#"<span class="boneyard">{}</span>"#,
+            text.replace('\n', "<br />")
+        )
+    } else {
+        "".to_string()
+    }
+}
+
+/// Exports all notes of the [Screenplay] as a list of footnotes, in the same order as they are
+/// marked in the document.
+fn export_footnotes(screenplay: &Screenplay, options: &ExportOptions) -> String {
+    let mut footnotes = Vec::new();
+    if let Some(titlepage) = &screenplay.titlepage {
+        let extra = |key: &str| titlepage.get(key).unwrap_or_default();
+        for s in extra("Series")
+            .iter()
+            .chain(&titlepage.title)
+            .chain(extra("Episode"))
+            .chain(&titlepage.credit)
+            .chain(&titlepage.authors)
+            .chain(&titlepage.source)
+            .chain(&titlepage.draft_date)
+            .chain(&titlepage.contact)
+            .chain(extra("Revision"))
+            .chain(extra("Notes"))
+            .chain(extra("Copyright"))
+        {
+            collect_notes(s, &mut footnotes);
+        }
+    }
+
+    for element in &screenplay.elements {
+        match &element.node {
+            Element::Heading { slug: s, .. }
+            | Element::Action(s)
+            | Element::Lyrics(s)
+            | Element::Transition(s)
+            | Element::CenteredText(s) => collect_notes(s, &mut footnotes),
+            Element::Synopsis(s) => {
+                if options.synopses {
+                    collect_notes(s, &mut footnotes)
+                }
+            }
+            Element::Section { title, .. } => {
+                if options.sections {
+                    collect_notes(title, &mut footnotes)
+                }
+            }
+            Element::Dialogue(dialogue) => collect_dialogue_notes(dialogue, &mut footnotes),
+            Element::DualDialogue(dialogue1, dialogue2) => {
+                collect_dialogue_notes(dialogue1, &mut footnotes);
+                collect_dialogue_notes(dialogue2, &mut footnotes);
+            }
+            Element::Note(s) => footnotes.push(s.plain_text()),
+            Element::Boneyard(_) | Element::PageBreak => (),
         }
-        DialogueElement::Line(s) => format!(r#"<p>{}</p>"#, format_rich_string(s)),
+    }
+
+    let content = footnotes
+        .iter()
+        .map(|text| format!("<li>{text}</li>"))
+        .collect::<Vec<String>>()
+        .concat();
+
+    format!(r#"<ol class="notes">{content}</ol>"#)
+}
+
+/// Collects the texts of all notes in a [Dialogue], in the order they are written out.
+fn collect_dialogue_notes(dialogue: &Dialogue, footnotes: &mut Vec<String>) {
+    collect_notes(&dialogue.character, footnotes);
+    if let Some(extension) = &dialogue.extension {
+        collect_notes(extension, footnotes);
+    }
+    for element in &dialogue.elements {
+        let (DialogueElement::Parenthetical(s) | DialogueElement::Line(s)) = &element.node;
+        collect_notes(s, footnotes);
+    }
+}
+
+/// Collects the texts of all notes in a [RichString].
+fn collect_notes(str: &RichString, footnotes: &mut Vec<String>) {
+    footnotes.extend(
+        str.elements
+            .iter()
+            .filter(|e| e.is_note())
+            .map(|e| e.text.clone()),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html(src: &str, options: &ExportOptions) -> String {
+        let mut out = Vec::new();
+        export_html(&crate::parse(src), &mut out, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn leaves_out_sections_by_default() {
+        let src = "# Act One\n\nINT. HOUSE - DAY\n\nBob waits.";
+
+        let default = html(src, &ExportOptions::new());
+        assert!(!default.contains("Act One"));
+        assert!(default.contains("Bob waits."));
+
+        let sections = html(src, &ExportOptions::new().sections(true));
+        assert!(sections.contains("<h1>Act One</h1>"));
+    }
+
+    #[test]
+    fn leaves_out_scene_numbers() {
+        let src = "INT. HOUSE - DAY #1#\n\nBob waits.";
+
+        let default = html(src, &ExportOptions::new());
+        assert!(default.contains(r#"<span class="scnuml">1</span>"#));
+
+        let without = html(src, &ExportOptions::new().scene_numbers(false));
+        assert!(!without.contains(r#"<span class="scnuml">"#));
+        assert!(without.contains("<h6>INT. HOUSE - DAY</h6>"));
+    }
+
+    #[test]
+    fn styles_by_theme() {
+        let src = "Bob waits.";
+
+        let default = html(src, &ExportOptions::new());
+        assert!(default.contains(CSS));
+
+        let plain = html(src, &ExportOptions::new().theme(Theme::Plain));
+        assert!(!plain.contains("<style"));
+
+        let css = "h6 { color: red; }".to_string();
+        let custom = html(src, &ExportOptions::new().theme(Theme::Css(css)));
+        assert!(custom.contains(r#"<style type="text/css">h6 { color: red; }</style>"#));
+        assert!(!custom.contains(CSS));
     }
 }
@@ -0,0 +1,302 @@
+//! This module implements the [Layout] of a [Screenplay], meaning where its scenes end up on the
+//! pages of the printed document, as measured from the document laid out by [typst].
+
+use std::collections::HashSet;
+
+use typst::{
+    foundations::{Content, Label, Selector, Value},
+    introspection::{Introspector, MetadataElem},
+    utils::PicoStr,
+};
+
+use crate::{
+    error::Result,
+    export::{
+        ExportOptions,
+        typst::{PageGeometry, SCENE_LABEL, compile, format_as_typst},
+    },
+    screenplay::Screenplay,
+};
+
+/// The label of the metadata marking the end of the screenplay, so that the length of the last
+/// scene can be measured.
+const END_LABEL: &str = "rustwell-end";
+
+/// The label of the metadata placed at the top of the text area of the last page, holding its
+/// height, so that the margins the template actually uses are measured.
+const BODY_LABEL: &str = "rustwell-body";
+
+/// The marks appended to the document, labelled with [END_LABEL] and [BODY_LABEL].
+const MARKS: &str = "#metadata(none)<rustwell-end>
+#place(top, layout(size => [#metadata(size.height)<rustwell-body>]))";
+
+/// Where the scenes of a [Screenplay] are laid out on the pages of the printed document, as
+/// produced by [export_pdf](crate::export_pdf) with the same options.
+///
+/// Pages are numbered as they are printed, so the first page after the title page is page 1.
+///
+/// # Examples
+///
+/// ```
+/// use rustwell::{ExportOptions, Layout, parse};
+///
+/// let screenplay = parse("INT. HOUSE - DAY\n\nBob waits.\n\nEXT. GARDEN - NIGHT\n\nRain.");
+/// let layout = Layout::new(&screenplay, &ExportOptions::new())?;
+///
+/// assert_eq!(layout.pages, 1);
+/// assert_eq!(layout.headings[1].element, 2);
+/// assert_eq!(layout.eighths(), [1, 1]);
+/// # Ok::<(), rustwell::Error>(())
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Layout {
+    /// The number of pages, not counting the title page.
+    pub pages: usize,
+    /// Where each scene heading is laid out, in the order of the scenes.
+    pub headings: Vec<HeadingPosition>,
+    /// Where the last element of the screenplay ends.
+    pub end: PagePosition,
+    /// The height of the text on a full page, between the top and bottom margins, in points.
+    pub body_height: f64,
+}
+
+/// Where an [Element::Heading](crate::screenplay::Element::Heading) is laid out.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeadingPosition {
+    /// The index of the heading in [Screenplay::elements].
+    pub element: usize,
+    /// Where the first line of the heading is laid out.
+    pub position: PagePosition,
+}
+
+/// A vertical position on a page.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PagePosition {
+    /// The page, where 1 is the first page after the title page.
+    pub page: usize,
+    /// The distance from the top margin, in points.
+    pub y: f64,
+}
+
+impl Layout {
+    /// Lays out the [Screenplay] with [typst], exactly like [export_pdf](crate::export_pdf)
+    /// does with the same options, and measures where its scene headings end up.
+    ///
+    /// Scenes are found by the marks Rustwell writes into their headings, so a custom template
+    /// is measured just as well, as long as it prints the headings it is given. Scenes whose
+    /// heading is not printed are left out, and a heading printed more than once, like in an
+    /// outline, counts where it is printed first.
+    ///
+    /// Fails like [export_pdf](crate::export_pdf).
+    pub fn new(screenplay: &Screenplay, options: &ExportOptions) -> Result<Self> {
+        let content = format_as_typst(screenplay, options)?;
+        let document = compile(format!("{content}\n{MARKS}"), options)?;
+        let introspector = &document.introspector;
+        let marks = |name: &str| {
+            let label = Label::new(PicoStr::intern(name)).expect("labels are not empty");
+            introspector.query(&Selector::Label(label))
+        };
+
+        // The text area is measured where the template puts it, and only taken from the paper
+        // size if the template hides the mark
+        let body = marks(BODY_LABEL).last().and_then(|mark| {
+            let Value::Length(height) = metadata(mark)? else {
+                return None;
+            };
+            let top = introspector.position(mark.location()?).point.y;
+            Some((top.to_pt(), height.abs.to_pt()))
+        });
+        let (top, body_height) = match body {
+            Some(body) => body,
+            None => {
+                let page = PageGeometry::new(options.paper)?;
+                (page.top, page.text_height())
+            }
+        };
+
+        // The title page is not counted, just as it is not numbered when printed
+        let offset = usize::from(screenplay.titlepage.is_some());
+        let position = |content: &Content| {
+            page_position(introspector, content, offset, top).unwrap_or_default()
+        };
+
+        let mut seen = HashSet::new();
+        let headings = marks(SCENE_LABEL)
+            .iter()
+            .filter_map(|mark| {
+                let &Value::Int(element) = metadata(mark)? else {
+                    return None;
+                };
+                let element = usize::try_from(element).ok()?;
+                seen.insert(element).then(|| HeadingPosition {
+                    element,
+                    position: position(mark),
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            pages: document.pages.len().saturating_sub(offset),
+            headings,
+            end: marks(END_LABEL).last().map(position).unwrap_or_default(),
+            body_height,
+        })
+    }
+
+    /// The length of each scene in eighths of a page, in the order of the scenes, as used for
+    /// breakdown sheets and stripboards.
+    ///
+    /// A scene is measured from its heading to the heading of the next scene, or to the end of
+    /// the screenplay for the last scene, and every page it crosses counts as a full page.
+    /// Lengths are rounded to the nearest eighth, but no scene is shorter than one eighth.
+    pub fn eighths(&self) -> Vec<usize> {
+        let ends = self
+            .headings
+            .iter()
+            .skip(1)
+            .map(|h| h.position)
+            .chain([self.end]);
+        self.headings
+            .iter()
+            .zip(ends)
+            .map(|(heading, end)| {
+                let start = heading.position;
+                let pages = end.page.saturating_sub(start.page) as f64;
+                let length = pages * self.body_height + end.y - start.y;
+                let eighths = (8.0 * length / self.body_height).round();
+                eighths.max(1.0) as usize
+            })
+            .collect()
+    }
+}
+
+/// Formats a length in eighths of a page the way it is written on breakdown sheets, like
+/// `1 3/8`, `5/8` or `2`.
+///
+/// # Examples
+///
+/// ```
+/// use rustwell::format_eighths;
+///
+/// assert_eq!(format_eighths(11), "1 3/8");
+/// assert_eq!(format_eighths(5), "5/8");
+/// assert_eq!(format_eighths(16), "2");
+/// ```
+pub fn format_eighths(eighths: usize) -> String {
+    match (eighths / 8, eighths % 8) {
+        (0, rest) => format!("{rest}/8"),
+        (pages, 0) => pages.to_string(),
+        (pages, rest) => format!("{pages} {rest}/8"),
+    }
+}
+
+/// The value of a mark, if it is [MetadataElem].
+fn metadata(mark: &Content) -> Option<&Value> {
+    mark.to_packed::<MetadataElem>()
+        .map(|metadata| &metadata.value)
+}
+
+/// Finds where some located [Content] is laid out, if it is laid out after the title page.
+fn page_position(
+    introspector: &Introspector,
+    content: &Content,
+    offset: usize,
+    top_margin: f64,
+) -> Option<PagePosition> {
+    let position = introspector.position(content.location()?);
+    Some(PagePosition {
+        page: position.page.get().checked_sub(offset)?,
+        y: position.point.y.to_pt() - top_margin,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::typst::TEMPLATE;
+
+    fn at(page: usize, y: f64) -> PagePosition {
+        PagePosition { page, y }
+    }
+
+    #[test]
+    fn measures_scenes_across_pages() {
+        let heading = |element, position| HeadingPosition { element, position };
+        let layout = Layout {
+            pages: 3,
+            headings: vec![
+                heading(0, at(1, 0.0)),
+                heading(4, at(1, 100.0)),
+                heading(9, at(1, 110.0)),
+                heading(12, at(3, 500.0)),
+            ],
+            end: at(3, 700.0),
+            body_height: 800.0,
+        };
+
+        assert_eq!(layout.eighths(), [1, 1, 20, 2]);
+        assert_eq!(format_eighths(20), "2 4/8");
+    }
+
+    #[test]
+    fn lays_out_scenes_across_pages() {
+        let scene = |n| format!("INT. ROOM {n} - DAY\n\n{}", "BOB\nI pace.\n\n".repeat(8));
+        let body = (1..=6).map(scene).collect::<String>();
+        let options = ExportOptions::new();
+        let layout = Layout::new(&crate::parse(&body), &options).unwrap();
+
+        assert!(layout.pages > 2);
+        assert_eq!(layout.headings.len(), 6);
+        assert_eq!(layout.headings[0].position.page, 1);
+        assert!(layout.headings[0].position.y < 1.0);
+        let key = |h: &HeadingPosition| (h.position.page, h.position.y);
+        assert!(layout.headings.windows(2).all(|w| key(&w[0]) < key(&w[1])));
+        assert!(layout.headings[5].position.page > 1);
+        assert_eq!(layout.end.page, layout.pages);
+
+        let eighths = layout.eighths().iter().sum::<usize>();
+        assert!(eighths > 8 * (layout.pages - 1) && eighths <= 8 * layout.pages + 6);
+
+        // The title page is not counted, so the scenes are laid out on the same pages
+        let titled = crate::parse(format!("Title: Rooms\n\n{body}"));
+        assert_eq!(Layout::new(&titled, &options).unwrap(), layout);
+    }
+
+    #[test]
+    fn finds_scenes_of_custom_templates() {
+        let scene = |n| format!("INT. ROOM {n} - DAY\n\n{}", "BOB\nI pace.\n\n".repeat(8));
+        let body = (1..=4).map(scene).collect::<String>();
+        let screenplay = crate::parse(format!("# Act One\n\n{body}"));
+
+        // Scenes without `heading`, a section with it, and a larger top margin
+        let template = TEMPLATE
+            .replace("heading(block(upper(cont)", "(block(upper(cont)")
+            .replace(
+                r#"text(fill: luma(100), weight: "bold", cont)"#,
+                "heading(cont)",
+            )
+            .replace(
+                "margin: margin,",
+                "margin: (top: 2in, bottom: 0.5in, x: 1in),",
+            );
+        let options = ExportOptions::new().sections(true).template(template);
+        let layout = Layout::new(&screenplay, &options).unwrap();
+
+        let elements = layout
+            .headings
+            .iter()
+            .map(|h| h.element)
+            .collect::<Vec<_>>();
+        assert_eq!(elements, [1, 10, 19, 28]);
+        assert_eq!(layout.body_height, 792.0 - 144.0 - 36.0);
+        assert!(layout.headings[0].position.y > 0.0);
+        let key = |h: &HeadingPosition| (h.position.page, h.position.y);
+        assert!(layout.headings.windows(2).all(|w| key(&w[0]) < key(&w[1])));
+
+        let eighths = layout.eighths().iter().sum::<usize>();
+        assert!(eighths > 8 * (layout.pages - 1) && eighths <= 8 * layout.pages + 4);
+    }
+}
@@ -2,12 +2,61 @@ use std::io::Write;
 
 use typst_pdf::PdfOptions;
 
-use crate::{export::typst::compile_document, screenplay::Screenplay};
+use crate::{
+    error::{Error, Result},
+    export::{
+        ExportOptions, Exporter,
+        typst::{compile_document, missing_glyphs},
+    },
+    screenplay::Screenplay,
+};
 
 /// Exports a `pdf` file and writes it to the provided writer. This is done by first constructing
 /// a [typst] document and then, using [typst], exporting that to an actual `pdf` document.
-pub fn export_pdf(screenplay: &Screenplay, mut writer: impl Write, synopses: bool) {
-    let compiled_doc = compile_document(screenplay, synopses);
-    let pdf = typst_pdf::pdf(&compiled_doc, &PdfOptions::default()).expect("Error generating PDF");
-    writer.write_all(&pdf).expect("Error writing PDF");
+///
+/// Fails with [Error::Compile] or [Error::Pdf] if [typst] could not produce the document, with
+/// [Error::Template] if the template of the [ExportOptions] does not fit, with [Error::Paper] if
+/// its paper size is invalid, with [Error::Font] or [Error::UnknownFont] if its fonts could not
+/// be used, and with [Error::Io] if the writer fails or a font could not be read.
+pub fn export_pdf(
+    screenplay: &Screenplay,
+    mut writer: impl Write,
+    options: &ExportOptions,
+) -> Result<()> {
+    let compiled_doc = compile_document(screenplay, options)?;
+    let pdf = typst_pdf::pdf(&compiled_doc, &PdfOptions::default())
+        .map_err(|errors| Error::Pdf(errors.to_vec()))?;
+    writer.write_all(&pdf)?;
+    Ok(())
+}
+
+/// The [Exporter] for `pdf` documents, see [export_pdf].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PdfExporter;
+
+impl Exporter for PdfExporter {
+    fn name(&self) -> &str {
+        "pdf"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pdf"]
+    }
+
+    fn export(
+        &self,
+        screenplay: &Screenplay,
+        writer: &mut dyn Write,
+        options: &ExportOptions,
+    ) -> Result<()> {
+        export_pdf(screenplay, writer, options)
+    }
+
+    fn missing_glyphs(
+        &self,
+        screenplay: &Screenplay,
+        options: &ExportOptions,
+    ) -> Result<Vec<char>> {
+        missing_glyphs(screenplay, options)
+    }
 }
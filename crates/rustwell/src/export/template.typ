@@ -21,8 +21,16 @@
 
 #let line_spacing(blanks) = 0.65em + blanks * 1.23em
 #let dialogue_counter = counter("dialogue")
+// How much the indentation of dialogue, parentheticals and lyrics is scaled to fit the page
+#let page_scale = state("page_scale", 1)
 
 #let screenplay(
+  width: 8.5in,
+  height: 11in,
+  margin: (top: 1in, left: 1.5in, right: 1in, bottom: 0.5in),
+  scale: 1,
+  font: "Courier Prime",
+  fallback: (),
   titlepage: false,
   title: none,
   credit: none,
@@ -30,14 +38,23 @@
   source: none,
   draft_date: none,
   contact: none,
+  series: none,
+  episode: none,
+  revision: none,
+  notes: none,
+  copyright: none,
   doc,
 ) = {
   set page(
-    margin: ( top: 1in, left: 1.5in, right: 1in, bottom: 0.5in),
+    width: width,
+    height: height,
+    margin: margin,
     header-ascent: 0.5in,
   )
+  page_scale.update(scale)
+  // Courier Prime, and then the fallback fonts, are used for glyphs missing in the chosen font
   set text(
-    font: "Courier Prime",
+    font: (font, "Courier Prime", ..fallback),
     size: 12pt,
   )
   set par(spacing: line_spacing(1))
@@ -51,7 +68,13 @@
   if titlepage {
     page({
       align(center, {
+        if series != none {
+        block(upper(series), below: 0.3in)
+      }
         upper(title)
+        if episode != none {
+        block(episode, above: 0.3in)
+      }
 
         if credit != none {
         block(credit, above: 1in)
@@ -67,8 +90,13 @@
       }
       })
 
-      if contact != none {
-      align(bottom, box(align(left, contact)))
+      let bottom_right = (revision, notes, copyright).filter(value => value != none)
+      if contact != none or bottom_right.len() > 0 {
+      align(bottom, grid(
+        columns: (1fr, 1fr),
+        align(left, if contact != none { contact }),
+        stack(spacing: line_spacing(1), ..bottom_right.map(value => align(right, value))),
+      ))
     }
       counter(page).update(0)
     }, margin: ( top: 2in, bottom: 1in ))
@@ -88,6 +116,7 @@
 #let dialogue_raw(character, dialogue, paren: none, left_inset, left_inset_name, right_inset_name) = {
   context {
     set par(spacing: line_spacing(0))
+    let scale = page_scale.get()
     dialogue_counter.step()
     let dialogue_count = dialogue_counter.get().at(0)
     let dialogue_header_counter = counter("dialogue_header" + str(dialogue_count))
@@ -104,7 +133,7 @@
         if paren != none {
           [ (#paren)]
         }
-      })]), inset: (left: left_inset))),
+      })]), inset: (left: left_inset * scale))),
       block(dialogue, spacing: line_spacing(0)),
       grid.footer(block({
         dialogue_footer_counter.step()
@@ -113,8 +142,8 @@
             [(MORE)]
           }
         }
-      }, inset: (left: left_inset), spacing: line_spacing(0))),
-      inset: (left: left_inset_name, right: right_inset_name),
+      }, inset: (left: left_inset * scale), spacing: line_spacing(0))),
+      inset: (left: left_inset_name * scale, right: right_inset_name * scale),
       gutter: line_spacing(0),
     )
   }
@@ -129,10 +158,10 @@
 )
 
 #let lyrics(cont) = {
-  block(
+  context block(
     upper(cont),
     above: line_spacing(0),
-    inset: (left: 1in, right: 1.5in),
+    inset: (left: 1in * page_scale.get(), right: 1.5in * page_scale.get()),
   )
 }
 
@@ -140,7 +169,8 @@
   context{
     block(
       par([#content], hanging-indent: measure("(").width),
-      inset: (left: 0.5in, right: 1in), spacing: line_spacing(0)
+      inset: (left: 0.5in * page_scale.get(), right: 1in * page_scale.get()),
+      spacing: line_spacing(0)
     )
   }
 }
@@ -170,3 +200,10 @@
     text(fill: luma(100), cont)
   )
 }
+
+#let section(cont, depth: 1) = {
+  block(
+    inset: (left: (depth - 1) * 0.2in),
+    text(fill: luma(100), weight: "bold", cont)
+  )
+}
@@ -1,69 +1,300 @@
-use std::{collections::HashMap, io::Write};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    env, fs, io,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 use typst::{
     self, Library, LibraryExt,
     diag::{FileError, FileResult},
     foundations::{Bytes, Datetime},
     layout::PagedDocument,
-    syntax::{FileId, Source, VirtualPath},
-    text::{Font, FontBook, FontInfo},
+    syntax::{FileId, Source, VirtualPath, ast},
+    text::{Font, FontBook},
     utils::LazyHash,
 };
 
 use crate::{
+    error::{Error, Result},
+    export::{ExportOptions, Exporter, NoteStyle, PaperSize},
     rich_string::{self, RichString},
-    screenplay::{DialogueElement, Element, Screenplay},
+    screenplay::{DialogueElement, Element, Screenplay, Visit},
+    span::Spanned,
 };
 
 /// The contents of the [typst] template `template.typ` found in the
 /// export module.
-const TEMPLATE: &str = include_str!("template.typ");
+pub(super) const TEMPLATE: &str = include_str!("template.typ");
+
+/// A function of the template, with the number of positional arguments and the named arguments
+/// it is called with.
+struct TemplateFunction {
+    name: &'static str,
+    positional: usize,
+    named: &'static [&'static str],
+}
+
+/// The functions every template has to define, see [ExportOptions::template].
+const CONTRACT: [TemplateFunction; 9] = [
+    TemplateFunction {
+        name: "screenplay",
+        positional: 1,
+        named: &[
+            "width",
+            "height",
+            "margin",
+            "scale",
+            "font",
+            "fallback",
+            "titlepage",
+            "title",
+            "credit",
+            "authors",
+            "source",
+            "draft_date",
+            "contact",
+            "series",
+            "episode",
+            "revision",
+            "notes",
+            "copyright",
+        ],
+    },
+    TemplateFunction {
+        name: "scene",
+        positional: 1,
+        named: &["number"],
+    },
+    TemplateFunction {
+        name: "dialogue",
+        positional: 2,
+        named: &["paren"],
+    },
+    TemplateFunction {
+        name: "dual_dialogue",
+        positional: 4,
+        named: &["paren1", "paren2"],
+    },
+    TemplateFunction {
+        name: "lyrics",
+        positional: 1,
+        named: &[],
+    },
+    TemplateFunction {
+        name: "transition",
+        positional: 1,
+        named: &[],
+    },
+    TemplateFunction {
+        name: "centered",
+        positional: 1,
+        named: &[],
+    },
+    TemplateFunction {
+        name: "synopsis",
+        positional: 1,
+        named: &[],
+    },
+    TemplateFunction {
+        name: "parenthetical",
+        positional: 1,
+        named: &[],
+    },
+];
+
+/// The function every template has to define when sections are included.
+const SECTION: TemplateFunction = TemplateFunction {
+    name: "section",
+    positional: 1,
+    named: &["depth"],
+};
 
 /// Exports the provided [Screenplay] as a pure [typst] document that can be
 /// manually compiled with any [typst]-compiler. The document will not be very
 /// readable nor be provided with comments explaining anything. This is mainly included
-/// for debugging.
-pub fn export_typst(screenplay: &Screenplay, mut writer: impl Write, synopses: bool) {
-    let content = format_as_typst(screenplay, synopses);
-    write!(writer, "{content}").expect("Failed to write to typst document");
+/// for debugging. Fails with [Error::Io] if the writer fails, with [Error::Template] if the
+/// template of the [ExportOptions] does not fit, and with [Error::Paper] if its paper size is
+/// invalid.
+pub fn export_typst(
+    screenplay: &Screenplay,
+    mut writer: impl Write,
+    options: &ExportOptions,
+) -> Result<()> {
+    let content = format_as_typst(screenplay, options)?;
+    write!(writer, "{content}")?;
+    Ok(())
+}
+
+/// The [Exporter] for [typst] documents, see [export_typst].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TypstExporter;
+
+impl Exporter for TypstExporter {
+    fn name(&self) -> &str {
+        "typst"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["typ"]
+    }
+
+    fn export(
+        &self,
+        screenplay: &Screenplay,
+        writer: &mut dyn Write,
+        options: &ExportOptions,
+    ) -> Result<()> {
+        export_typst(screenplay, writer, options)
+    }
 }
 
 /// Generates a [PagedDocument], which is a layouted [typst] document which can then
-/// be exported and written with any [typst] exporter, like [typst_pdf].
-pub fn compile_document(screenplay: &Screenplay, synopses: bool) -> PagedDocument {
-    let (fontbook, fonts) = create_fontbook();
-    let content = format_as_typst(screenplay, synopses);
-    let worldplay = WorldPlay::new(content, &fontbook, &fonts);
+/// be exported and written with any [typst] exporter, like [typst_pdf]. Fails with
+/// [Error::Compile] if [typst] could not compile the document, with [Error::Template] if the
+/// template of the [ExportOptions] does not fit, with [Error::Paper] if its paper size is
+/// invalid, and with [Error::Font], [Error::UnknownFont] or [Error::Io] if the fonts of the
+/// [ExportOptions] could not be used.
+pub fn compile_document(screenplay: &Screenplay, options: &ExportOptions) -> Result<PagedDocument> {
+    compile(format_as_typst(screenplay, options)?, options)
+}
+
+/// Compiles [typst] code, as formatted by [format_as_typst], into a [PagedDocument] with the
+/// fonts of the [ExportOptions]. Fails like [compile_document].
+pub(super) fn compile(content: String, options: &ExportOptions) -> Result<PagedDocument> {
+    let (fontbook, fonts) = &create_fontbook(options)?;
+    if let Some(family) = &options.font
+        && !fontbook.contains_family(family)
+    {
+        return Err(Error::UnknownFont(family.clone()));
+    }
+
+    let worldplay = WorldPlay::new(content, fontbook, fonts);
     typst::compile(&worldplay)
         .output
-        .expect("Error compiling typst document")
+        .map_err(|errors| Error::Compile(errors.to_vec()))
 }
 
 /// Formats the [Screenplay] as a [typst] document, meaning it essentially gets
-/// converted into [typst]-compilable code.
-fn format_as_typst(screenplay: &Screenplay, synopses: bool) -> String {
+/// converted into [typst]-compilable code. Fails with [Error::Template] if the template of the
+/// [ExportOptions] does not fit, and with [Error::Paper] if its paper size is invalid.
+pub(super) fn format_as_typst(screenplay: &Screenplay, options: &ExportOptions) -> Result<String> {
+    let template = match &options.template {
+        Some(template) => {
+            validate_template(template, options)?;
+            template
+        }
+        None => TEMPLATE,
+    };
     let formatted_elements = screenplay
         .elements
         .iter()
-        .map(|e| export_element(e, synopses))
+        .enumerate()
+        .map(|(index, e)| export_element(e, index, options))
         .collect::<Vec<String>>();
-    let titlepage = export_titlepage(screenplay);
-    format!("{TEMPLATE}\n{titlepage}\n{}", formatted_elements.join("\n"))
+    let titlepage = export_titlepage(screenplay, options)?;
+    Ok(format!(
+        "{template}\n{titlepage}\n{}",
+        formatted_elements.join("\n")
+    ))
+}
+
+/// Checks that a template defines every function of the [CONTRACT], taking the arguments it is
+/// called with, so that a template that does not fit is reported by what it lacks rather than
+/// by [typst] failing somewhere in the generated code. Names bound to anything but a closure,
+/// like `heading.with(..)`, cannot be checked, and are taken to be fitting functions.
+fn validate_template(template: &str, options: &ExportOptions) -> Result<()> {
+    let root = typst::syntax::parse(template);
+    if let Some(error) = root.errors().first() {
+        return Err(Error::Template(format!("syntax error: {}", error.message)));
+    }
+
+    // Later definitions shadow earlier ones, just as they do in typst
+    let mut functions = HashMap::new();
+    let markup = root.cast::<ast::Markup>().into_iter();
+    for expr in markup.flat_map(|markup| markup.exprs()) {
+        if let ast::Expr::LetBinding(binding) = expr {
+            let closure = match binding.init() {
+                Some(ast::Expr::Closure(closure)) => Some(closure),
+                _ => None,
+            };
+            for name in binding.kind().bindings() {
+                functions.insert(name.as_str(), closure);
+            }
+        }
+    }
+
+    let sections = options.sections.then_some(&SECTION);
+    for function in CONTRACT.iter().chain(sections) {
+        let Some(closure) = functions.get(function.name) else {
+            return Err(Error::Template(format!(
+                "`{}` is not defined",
+                function.name
+            )));
+        };
+        if let Some(closure) = closure {
+            check_arguments(function, closure)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a function of the template takes the arguments of its [TemplateFunction], where
+/// a spread parameter (`..args`) takes any arguments.
+fn check_arguments(function: &TemplateFunction, closure: &ast::Closure) -> Result<()> {
+    let mut positional = 0;
+    let mut named = Vec::new();
+    for param in closure.params().children() {
+        match param {
+            ast::Param::Pos(_) => positional += 1,
+            ast::Param::Named(param) => named.push(param.name().as_str()),
+            ast::Param::Spread(_) => return Ok(()),
+        }
+    }
+
+    if positional != function.positional {
+        return Err(Error::Template(format!(
+            "`{}` takes {positional} positional arguments, but is called with {}",
+            function.name, function.positional
+        )));
+    }
+    if let Some(missing) = function.named.iter().find(|name| !named.contains(*name)) {
+        return Err(Error::Template(format!(
+            "`{}` does not take the named argument `{missing}`",
+            function.name
+        )));
+    }
+    Ok(())
 }
 
 /// Exports the [crate::screenplay::TitlePage] in the provided [Screenplay] to [typst] code.
 /// This function also provides the necessary `#show: screenplay.with(...)` that
-/// handles the page layout for the whole screenplay.
-fn export_titlepage(screenplay: &Screenplay) -> String {
+/// handles the page layout for the whole screenplay. Fails with [Error::Paper] if the
+/// [PaperSize] of the [ExportOptions] is invalid.
+fn export_titlepage(screenplay: &Screenplay, options: &ExportOptions) -> Result<String> {
+    let (notes, cuts) = (options.notes, options.cuts);
+    let settings = format!(
+        "{}, font: {}, fallback: {}",
+        format_page(PageGeometry::new(options.paper)?),
+        format_string(options.font.as_deref().unwrap_or(DEFAULT_FONT)),
+        format_array(&options.fallback_fonts),
+    );
     if let Some(titlepage) = &screenplay.titlepage {
-        let title = format_titlepage_element(&titlepage.title);
-        let credit = format_titlepage_element(&titlepage.credit);
-        let authors = format_titlepage_element(&titlepage.authors);
-        let source = format_titlepage_element(&titlepage.source);
-        let draft_date = format_titlepage_element(&titlepage.draft_date);
-        let contact = format_titlepage_element(&titlepage.contact);
-        format!(
+        let extra = |key: &str| titlepage.get(key).unwrap_or_default();
+        let title = format_titlepage_element(&titlepage.title, notes, cuts);
+        let credit = format_titlepage_element(&titlepage.credit, notes, cuts);
+        let authors = format_titlepage_element(&titlepage.authors, notes, cuts);
+        let source = format_titlepage_element(&titlepage.source, notes, cuts);
+        let draft_date = format_titlepage_element(&titlepage.draft_date, notes, cuts);
+        let contact = format_titlepage_element(&titlepage.contact, notes, cuts);
+        let series = format_titlepage_element(extra("Series"), notes, cuts);
+        let episode = format_titlepage_element(extra("Episode"), notes, cuts);
+        let revision = format_titlepage_element(extra("Revision"), notes, cuts);
+        let title_notes = format_titlepage_element(extra("Notes"), notes, cuts);
+        let copyright = format_titlepage_element(extra("Copyright"), notes, cuts);
+        Ok(format!(
             r#"#show: screenplay.with(
+  {settings},
   titlepage: true,
   title: {title},
   credit: {credit},
@@ -71,92 +302,197 @@ fn export_titlepage(screenplay: &Screenplay) -> String {
   source: {source},
   draft_date: {draft_date},
   contact: {contact},
+  series: {series},
+  episode: {episode},
+  revision: {revision},
+  notes: {title_notes},
+  copyright: {copyright},
 )"#
-        )
+        ))
     } else {
-        "#show: screenplay.with(titlepage: false)".to_string()
+        Ok(format!(
+            "#show: screenplay.with({settings}, titlepage: false)"
+        ))
+    }
+}
+
+/// The size and margins of the pages for a [PaperSize], in points, along with how much the
+/// indentation of dialogue, parentheticals and lyrics is scaled.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(super) struct PageGeometry {
+    pub width: f64,
+    pub height: f64,
+    pub top: f64,
+    pub left: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub scale: f64,
+}
+
+impl PageGeometry {
+    /// US Letter, with the 1.5in left margin leaving room for binding, and a text area of 6 by
+    /// 9.5 inches. All other sizes are based on it.
+    const LETTER: PageGeometry = PageGeometry {
+        width: 612.0,
+        height: 792.0,
+        top: 72.0,
+        left: 108.0,
+        right: 72.0,
+        bottom: 36.0,
+        scale: 1.0,
+    };
+
+    /// Computes the [PageGeometry] of a [PaperSize]. Fails with [Error::Paper] if a custom
+    /// size is not positive and finite.
+    pub fn new(paper: PaperSize) -> Result<Self> {
+        let letter = Self::LETTER;
+        Ok(match paper {
+            PaperSize::Letter => letter,
+            // A4 is narrower and taller than Letter, so the text area of Letter is kept by
+            // narrowing the right margin and widening the bottom one
+            PaperSize::A4 => Self {
+                width: 595.28,
+                height: 841.89,
+                right: 595.28 - letter.left - letter.text_width(),
+                bottom: 841.89 - letter.top - letter.text_height(),
+                ..letter
+            },
+            PaperSize::Custom { width, height } => {
+                let valid = |length: f64| length.is_finite() && length > 0.0;
+                if !valid(width) || !valid(height) {
+                    return Err(Error::Paper { width, height });
+                }
+                let (x, y) = (width / letter.width, height / letter.height);
+                Self {
+                    width,
+                    height,
+                    top: letter.top * y,
+                    left: letter.left * x,
+                    right: letter.right * x,
+                    bottom: letter.bottom * y,
+                    scale: x,
+                }
+            }
+        })
+    }
+
+    /// The width of the text area, between the left and right margins.
+    pub fn text_width(&self) -> f64 {
+        self.width - self.left - self.right
+    }
+
+    /// The height of the text area, between the top and bottom margins.
+    pub fn text_height(&self) -> f64 {
+        self.height - self.top - self.bottom
     }
 }
 
+/// Formats the [PageGeometry] as the arguments of `screenplay` in the template.
+fn format_page(page: PageGeometry) -> String {
+    format!(
+        "width: {:.2}pt, height: {:.2}pt, \
+         margin: (top: {:.2}pt, left: {:.2}pt, right: {:.2}pt, bottom: {:.2}pt), scale: {:.4}",
+        page.width, page.height, page.top, page.left, page.right, page.bottom, page.scale
+    )
+}
+
 /// Exports a single [Element] as [typst] code. Primarily done by calling the associated
-/// [typst] function found in the template.
-fn export_element(element: &Element, synopses: bool) -> String {
+/// [typst] function found in the template. Scene headings are marked with their `index` in
+/// [Screenplay::elements], see [SCENE_LABEL].
+fn export_element(element: &Element, index: usize, options: &ExportOptions) -> String {
+    let (notes, cuts) = (options.notes, options.cuts);
     match element {
         Element::Heading { slug, number } => {
-            if let Some(num) = number {
-                format!(
-                    r#"#scene(number: "{}")[{}]"#,
-                    replace_escaping(num),
-                    format_rich_string(slug)
-                )
+            let slug = format!(
+                "#metadata({index})<{SCENE_LABEL}>{}",
+                format_rich_string(slug, notes, cuts)
+            );
+            if let Some(num) = number.as_ref().filter(|_| options.scene_numbers) {
+                format!(r#"#scene(number: "{}")[{slug}]"#, replace_escaping(num))
             } else {
-                format!("#scene[{}]", format_rich_string(slug))
+                format!("#scene[{slug}]")
             }
         }
-        Element::Action(s) => format_rich_string(s),
+        Element::Action(s) => format_rich_string(s, notes, cuts),
         Element::Dialogue(dialogue) => format!(
             "#dialogue(paren: {})[{}][{}]",
-            format_character_extension(&dialogue.extension),
-            format_rich_string(&dialogue.character),
-            format_dialogue(&dialogue.elements),
+            format_character_extension(&dialogue.extension, notes, cuts),
+            format_rich_string(&dialogue.character, notes, cuts),
+            format_dialogue(&dialogue.elements, notes, cuts),
         ),
         Element::DualDialogue(dialogue1, dialogue2) => format!(
             "#dual_dialogue(paren1: {}, paren2: {})[{}][{}][{}][{}]",
-            format_character_extension(&dialogue1.extension),
-            format_character_extension(&dialogue2.extension),
-            format_rich_string(&dialogue1.character),
-            format_dialogue(&dialogue1.elements),
-            format_rich_string(&dialogue2.character),
-            format_dialogue(&dialogue2.elements),
+            format_character_extension(&dialogue1.extension, notes, cuts),
+            format_character_extension(&dialogue2.extension, notes, cuts),
+            format_rich_string(&dialogue1.character, notes, cuts),
+            format_dialogue(&dialogue1.elements, notes, cuts),
+            format_rich_string(&dialogue2.character, notes, cuts),
+            format_dialogue(&dialogue2.elements, notes, cuts),
         ),
-        Element::Lyrics(s) => format!("#lyrics[{}]", format_rich_string(s)),
-        Element::Transition(s) => format!("#transition[{}]", format_rich_string(s)),
-        Element::CenteredText(s) => format!("#centered[{}]", format_rich_string(s)),
+        Element::Lyrics(s) => format!("#lyrics[{}]", format_rich_string(s, notes, cuts)),
+        Element::Transition(s) => format!("#transition[{}]", format_rich_string(s, notes, cuts)),
+        Element::CenteredText(s) => format!("#centered[{}]", format_rich_string(s, notes, cuts)),
         Element::Synopsis(s) => {
-            if synopses {
-                format!("#synopsis[{}]", format_rich_string(s))
+            if options.synopses {
+                format!("#synopsis[{}]", format_rich_string(s, notes, cuts))
             } else {
                 "".to_string()
             }
         }
+        Element::Note(s) => format_note(&s.plain_text(), notes),
+        Element::Boneyard(s) => format_boneyard(s, cuts),
         Element::PageBreak => "#pagebreak()".to_string(),
+        Element::Section { depth, title } => {
+            if options.sections {
+                format!(
+                    "#section(depth: {depth})[{}]",
+                    format_rich_string(title, notes, cuts)
+                )
+            } else {
+                "".to_string()
+            }
+        }
     }
 }
 
 /// Formats the dialogue into [typst] code.
-fn format_dialogue(dialogue: &[DialogueElement]) -> String {
+fn format_dialogue(dialogue: &[Spanned<DialogueElement>], notes: NoteStyle, cuts: bool) -> String {
     dialogue
         .iter()
-        .map(format_dialogue_element)
+        .map(|e| format_dialogue_element(e, notes, cuts))
         .collect::<Vec<String>>()
         .join(" ")
 }
 
 /// Formats the character extension (`(V.O)`, for example) that is
 /// next to a character's name in a dialogue.
-fn format_character_extension(opt_ext: &Option<RichString>) -> String {
+fn format_character_extension(
+    opt_ext: &Option<RichString>,
+    notes: NoteStyle,
+    cuts: bool,
+) -> String {
     if let Some(ext) = opt_ext {
-        format!("[{}]", format_rich_string(ext))
+        format!("[{}]", format_rich_string(ext, notes, cuts))
     } else {
         "none".to_string()
     }
 }
 
 /// Formats a [DialogueElement] into a [typst] code.
-fn format_dialogue_element(element: &DialogueElement) -> String {
+fn format_dialogue_element(element: &DialogueElement, notes: NoteStyle, cuts: bool) -> String {
     match element {
         DialogueElement::Parenthetical(s) => {
-            format!("#parenthetical[{}]", format_rich_string(s))
+            format!("#parenthetical[{}]", format_rich_string(s, notes, cuts))
         }
-        DialogueElement::Line(s) => format_rich_string(s),
+        DialogueElement::Line(s) => format_rich_string(s, notes, cuts),
     }
 }
 
 /// Formats a [RichString] into a [typst]-[String].
-fn format_rich_string(str: &RichString) -> String {
+fn format_rich_string(str: &RichString, notes: NoteStyle, cuts: bool) -> String {
     str.elements
         .iter()
-        .map(format_rich_element)
+        .map(|e| format_rich_element(e, notes, cuts))
         .collect::<Vec<String>>()
         .concat()
 }
@@ -168,12 +504,20 @@ fn format_rich_string(str: &RichString) -> String {
 ///
 /// This function also iterates over each string twice to replace all escaping
 /// characters `\` and `"` with `\\` and `\*` respectively.
-fn format_rich_element(element: &rich_string::Element) -> String {
+fn format_rich_element(element: &rich_string::Element, notes: NoteStyle, cuts: bool) -> String {
     // Assumes newlines '\n' will only occur sole elements
     if element.text == "\n" {
         return "\\ ".to_string();
     }
 
+    if element.is_note() {
+        return format_note(&element.text, notes);
+    }
+
+    if element.is_boneyard() {
+        return format_boneyard(&element.text, cuts);
+    }
+
     let mut out = format!(
         "#text({}{}\"{}\")",
         if element.is_bold() {
@@ -195,6 +539,45 @@ fn format_rich_element(element: &rich_string::Element) -> String {
     out
 }
 
+/// Formats the text of a note into [typst] code, according to the [NoteStyle].
+/// Margin notes are placed 0.1in into the right margin, at the height of where they were written.
+fn format_note(text: &str, notes: NoteStyle) -> String {
+    let text = replace_escaping(text);
+    match notes {
+        NoteStyle::Hidden => "".to_string(),
+        NoteStyle::Highlighted => format!("#highlight[#text(\"{text}\")]"),
+        NoteStyle::Margin => format!(
+            "#box(context place(dx: page.width - here().position().x - 0.9in, \
+             box(width: 0.8in, text(size: 8pt, fill: luma(100), \"{text}\"))))"
+        ),
+        NoteStyle::Footnotes => format!("#footnote[#text(\"{text}\")]"),
+    }
+}
+
+/// Formats the text of a boneyard into [typst] code, greyed out if cuts are shown.
+fn format_boneyard(text: &str, cuts: bool) -> String {
+    if cuts {
+        format!("#text(fill: luma(150), \"{}\")", replace_escaping(text))
+    } else {
+        "".to_string()
+    }
+}
+
+/// Formats a [typst] string, with the text in it escaped.
+fn format_string(s: &str) -> String {
+    format!("\"{}\"", replace_escaping(s))
+}
+
+/// Formats a [typst] array of strings. The trailing comma keeps an array of one string from
+/// being read as a parenthesized string.
+fn format_array(strings: &[String]) -> String {
+    let items: String = strings
+        .iter()
+        .map(|s| format!("{}, ", format_string(s)))
+        .collect();
+    format!("({})", items.trim_end())
+}
+
 /// This function also iterates over each string twice to replace all escaping
 /// characters `\` and `"` with `\\` and `\*` respectively.
 fn replace_escaping(s: &str) -> String {
@@ -203,7 +586,7 @@ fn replace_escaping(s: &str) -> String {
 
 /// Formats a single [crate::screenplay::TitlePage] element into [typst] code.
 /// If no value has been declared it will return `"none"`.
-fn format_titlepage_element(element: &[RichString]) -> String {
+fn format_titlepage_element(element: &[RichString], notes: NoteStyle, cuts: bool) -> String {
     if element.is_empty() {
         return "none".to_string();
     }
@@ -211,7 +594,7 @@ fn format_titlepage_element(element: &[RichString]) -> String {
         "[{}]",
         element
             .iter()
-            .map(format_rich_string)
+            .map(|s| format_rich_string(s, notes, cuts))
             .collect::<Vec<String>>()
             .join("\\ ")
     )
@@ -227,9 +610,21 @@ struct WorldPlay<'a> {
     fonts: &'a Vec<Font>,
 }
 
+/// The label of the invisible metadata written at the start of every scene heading, holding the
+/// index of the heading in [Screenplay::elements], so that scenes are found wherever and however
+/// the template lays out their headings.
+pub(super) const SCENE_LABEL: &str = "rustwell-scene";
+
 /// `MAIN` contains the "filename" of the main file, which in [typst] **has** to be `/main.typ`.
 const MAIN: &str = "/main.typ";
 
+/// The family of the font bundled with Rustwell, used when no other font is chosen, and for
+/// glyphs missing in the chosen font.
+const DEFAULT_FONT: &str = "Courier Prime";
+
+/// The extensions of the files loaded from font directories.
+const FONT_EXTENSIONS: [&str; 4] = ["ttf", "otf", "ttc", "otc"];
+
 /// The font bundled together with Rustwell; Courier Prime.
 /// Includes the data of the font styles Regular, Bold, Italic
 /// and BoldItalic, in stated order.
@@ -313,21 +708,377 @@ fn create_file_id(filename: &str) -> FileId {
     FileId::new(None, VirtualPath::new(filename))
 }
 
-/// Creates a [FontBook] which indexes the returned [Vec<Font>].
-fn create_fontbook() -> (LazyHash<FontBook>, Vec<Font>) {
+/// The fonts of printed documents, and the [FontBook] indexing them, as created by
+/// [create_fontbook].
+type LoadedFonts = (LazyHash<FontBook>, Vec<Font>);
+
+/// Creates a [FontBook] which indexes the returned [Vec<Font>], with the bundled Courier Prime
+/// first, then the fonts loaded from the font paths of the [ExportOptions], in order, and last
+/// the installed fonts of the chosen and fallback families if [ExportOptions::system_fonts] is
+/// set. Fails with [Error::Io] if a font path could not be read, and with [Error::Font] if a
+/// file in it is not a font.
+fn create_fontbook(options: &ExportOptions) -> Result<LoadedFonts> {
     let mut fonts = Vec::new();
+    for font_data in FONTS.iter() {
+        fonts.extend(Font::new(Bytes::new(font_data), 0));
+    }
+
+    for path in &options.font_paths {
+        for file in font_files(path)? {
+            let found = read_fonts(&file)?;
+            if found.is_empty() {
+                return Err(Error::Font(file));
+            }
+            fonts.extend(found);
+        }
+    }
+
+    if options.system_fonts {
+        let families: Vec<&str> = options
+            .font
+            .iter()
+            .chain(&options.fallback_fonts)
+            .map(String::as_str)
+            .collect();
+        // Installed fonts that cannot be read are skipped, as they are not the user's to fix
+        let dirs = system_font_dirs();
+        let files = dirs
+            .iter()
+            .flat_map(|dir| font_files(dir).unwrap_or_default());
+        for file in files {
+            let found = read_fonts(&file).unwrap_or_default();
+            fonts.extend(found.into_iter().filter(|font| {
+                let family = &font.info().family;
+                families.iter().any(|f| f.eq_ignore_ascii_case(family))
+            }));
+        }
+    }
+
     let mut fontbook = FontBook::new();
+    for font in &fonts {
+        fontbook.push(font.info().clone());
+    }
 
-    for font_data in FONTS.iter() {
-        let font = match Font::new(Bytes::new(font_data), 0) {
-            Some(f) => f,
-            None => continue,
-        };
-        fonts.push(font);
+    Ok((LazyHash::new(fontbook), fonts))
+}
+
+/// Lists the font files at a path, which is either the path itself, or all files with a font
+/// extension in a directory and its subdirectories.
+fn font_files(path: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    collect_font_files(path, &mut HashSet::new(), &mut files)?;
+    Ok(files)
+}
+
+/// Adds the font files at a path to `files`, like [font_files]. Directories that were `visited`
+/// already are skipped, so that symbolic links back to a parent directory are not followed
+/// forever.
+fn collect_font_files(
+    path: &Path,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let canonical = fs::canonicalize(path).map_err(|err| with_path(err, path))?;
+    if !visited.insert(canonical) {
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)
+        .and_then(|dir| {
+            dir.map(|entry| entry.map(|e| e.path()))
+                .collect::<io::Result<Vec<_>>>()
+        })
+        .map_err(|err| with_path(err, path))?;
+    // Sorted, so that the same fonts are picked whatever order the system lists them in
+    entries.sort();
+
+    for entry in entries {
+        let is_font = entry
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| FONT_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+        if entry.is_dir() {
+            collect_font_files(&entry, visited, files)?;
+        } else if is_font {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Reads every font of a font file, where collections (`ttc` and `otc`) hold several fonts.
+/// Files that are not fonts have none.
+fn read_fonts(path: &Path) -> Result<Vec<Font>> {
+    let data = fs::read(path).map_err(|err| with_path(err, path))?;
+    Ok(Font::iter(Bytes::new(data)).collect())
+}
+
+/// Adds the path to an [io::Error], which would otherwise not tell which font could not be read.
+fn with_path(err: io::Error, path: &Path) -> Error {
+    Error::Io(io::Error::new(
+        err.kind(),
+        format!("{}: {err}", path.display()),
+    ))
+}
+
+/// The directories fonts are installed in on Linux, macOS and Windows, for those that exist.
+fn system_font_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = [
+        "/usr/share/fonts",
+        "/usr/local/share/fonts",
+        "/Library/Fonts",
+        "/System/Library/Fonts",
+        "C:\\Windows\\Fonts",
+    ]
+    .map(PathBuf::from)
+    .to_vec();
+    if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+        dirs.extend([
+            home.join(".fonts"),
+            home.join(".local/share/fonts"),
+            home.join("Library/Fonts"),
+        ]);
+    }
+    if let Some(local) = env::var_os("LOCALAPPDATA").map(PathBuf::from) {
+        dirs.push(local.join("Microsoft\\Windows\\Fonts"));
+    }
+
+    dirs.retain(|dir| dir.is_dir());
+    dirs
+}
+
+/// Finds the characters printed from the [Screenplay] that no font has glyphs for, neither the
+/// bundled Courier Prime nor those loaded with the [ExportOptions], so that they can be reported
+/// before they are printed as blank boxes in a `pdf`. What the [ExportOptions] leave out, like
+/// hidden notes, is not checked. Fails with [Error::Io] or [Error::Font] if the fonts could not
+/// be loaded.
+///
+/// # Examples
+///
+/// ```
+/// use rustwell::{ExportOptions, missing_glyphs, parse};
+///
+/// let screenplay = parse("INT. HOUSE - DAY\n\nBob writes 你好 on the wall.");
+/// let missing = missing_glyphs(&screenplay, &ExportOptions::new())?;
+///
+/// assert_eq!(missing, ['你', '好']);
+/// # Ok::<(), rustwell::Error>(())
+/// ```
+pub fn missing_glyphs(screenplay: &Screenplay, options: &ExportOptions) -> Result<Vec<char>> {
+    let (_, fonts) = create_fontbook(options)?;
+
+    let mut characters = Characters {
+        options,
+        characters: BTreeSet::new(),
+    };
+    characters.visit_screenplay(screenplay);
+    Ok(characters
+        .characters
+        .into_iter()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .filter(|&c| {
+            !fonts
+                .iter()
+                .any(|font| font.info().coverage.contains(c as u32))
+        })
+        .collect())
+}
+
+/// A [Visit] collecting every character printed from a [Screenplay] with the [ExportOptions].
+struct Characters<'a> {
+    options: &'a ExportOptions,
+    characters: BTreeSet<char>,
+}
+
+impl Visit for Characters<'_> {
+    fn visit_section(&mut self, _depth: usize, title: &RichString) {
+        if self.options.sections {
+            self.visit_rich_string(title);
+        }
+    }
+
+    fn visit_synopsis(&mut self, synopsis: &RichString) {
+        if self.options.synopses {
+            self.visit_rich_string(synopsis);
+        }
+    }
+
+    fn visit_note(&mut self, note: &RichString) {
+        if self.options.notes != NoteStyle::Hidden {
+            self.visit_rich_string(note);
+        }
+    }
+
+    fn visit_boneyard(&mut self, boneyard: &str) {
+        if self.options.cuts {
+            self.characters.extend(boneyard.chars());
+        }
+    }
+
+    fn visit_run(&mut self, run: &rich_string::Element) {
+        let hidden = (run.is_note() && self.options.notes == NoteStyle::Hidden)
+            || (run.is_boneyard() && !self.options.cuts);
+        if !hidden {
+            self.characters.extend(run.text.chars());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseOptions;
+
+    #[test]
+    fn leaves_out_sections_by_default() {
+        let screenplay = crate::parse("# Act One\n\nINT. HOUSE - DAY\n\nBob waits.");
+
+        let default = format_as_typst(&screenplay, &ExportOptions::new()).unwrap();
+        assert!(!default.contains("#section("));
+        assert!(default.contains("#scene["));
+
+        let options = ExportOptions::new().sections(true);
+        let sections = format_as_typst(&screenplay, &options).unwrap();
+        assert!(sections.contains("#section(depth: 1)["));
+    }
+
+    #[test]
+    fn leaves_out_scene_numbers() {
+        let screenplay = crate::parse("INT. HOUSE - DAY #1#\n\nBob waits.");
+
+        let default = format_as_typst(&screenplay, &ExportOptions::new()).unwrap();
+        assert!(default.contains(r#"#scene(number: "1")[#metadata(0)<rustwell-scene>#text("#));
+
+        let options = ExportOptions::new().scene_numbers(false);
+        let without = format_as_typst(&screenplay, &options).unwrap();
+        assert!(without.contains("#scene[#metadata(0)<rustwell-scene>#text("));
+        assert!(!without.contains("#scene(number:"));
+    }
+
+    #[test]
+    fn validates_templates() {
+        let options = ExportOptions::new().sections(true);
+        assert!(validate_template(TEMPLATE, &options).is_ok());
+
+        let without_sections = TEMPLATE.replace("#let section(", "#let chapter(");
+        assert!(validate_template(&without_sections, &ExportOptions::new()).is_ok());
+        let missing = validate_template(&without_sections, &options);
+        assert!(matches!(missing, Err(Error::Template(m)) if m == "`section` is not defined"));
 
-        let info = FontInfo::new(font_data, 0).expect("Could not parse font");
-        fontbook.push(info);
+        let no_number = TEMPLATE.replace("#let scene(cont, number: none)", "#let scene(cont)");
+        let named = validate_template(&no_number, &options);
+        assert!(matches!(named, Err(Error::Template(m)) if m.contains("`number`")));
+
+        let spread = TEMPLATE.replace("#let scene(cont, number: none)", "#let scene(..args)");
+        assert!(validate_template(&spread, &options).is_ok());
+
+        let bound = "#let scene = (cont, number: none) =>";
+        let closure = TEMPLATE.replace("#let scene(cont, number: none) =", bound);
+        assert!(validate_template(&closure, &options).is_ok());
+        let no_number =
+            TEMPLATE.replace("#let scene(cont, number: none) =", "#let scene = cont =>");
+        let named = validate_template(&no_number, &options);
+        assert!(matches!(named, Err(Error::Template(m)) if m.contains("`number`")));
+    }
+
+    #[test]
+    fn formats_fallback_fonts() {
+        assert_eq!(format_array(&[]), "()");
+        assert_eq!(format_array(&["Noto Sans".into()]), "(\"Noto Sans\",)");
+        assert_eq!(
+            format_array(&["A \"B\"".into(), "C".into()]),
+            "(\"A \\\"B\\\"\", \"C\",)"
+        );
+    }
+
+    #[test]
+    fn keeps_text_area_on_a4() {
+        let letter = PageGeometry::new(PaperSize::Letter).unwrap();
+        let a4 = PageGeometry::new(PaperSize::A4).unwrap();
+
+        assert_eq!(a4.left, letter.left);
+        assert!((a4.text_width() - letter.text_width()).abs() < 1e-9);
+        assert!((a4.text_height() - letter.text_height()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn loads_font_directories() {
+        let options = ExportOptions::new().font_path("src/export/fonts");
+        let (fontbook, fonts) = create_fontbook(&options).unwrap();
+        assert_eq!(fonts.len(), 2 * FONTS.len());
+        assert!(fontbook.contains_family("courier prime"));
+
+        let not_a_font = create_fontbook(&ExportOptions::new().font_path("Cargo.toml"));
+        assert!(matches!(not_a_font, Err(Error::Font(path)) if path.ends_with("Cargo.toml")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stops_at_font_directory_loops() {
+        let dir = env::temp_dir().join(format!("rustwell-{}-fonts", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("nested/font.ttf"), "").unwrap();
+        let _ = std::os::unix::fs::symlink(&dir, dir.join("nested/parent"));
+
+        let files = font_files(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files.unwrap(), [dir.join("nested/font.ttf")]);
+    }
+
+    #[test]
+    fn checks_only_printed_glyphs() {
+        let src = "INT. HOUSE - DAY\n\nBob waits. [[你好]] /* 世 */\n\n/* 界 */";
+        let screenplay = crate::parse_with_options(
+            src,
+            ParseOptions {
+                boneyards: true,
+                ..Default::default()
+            },
+        );
+
+        let default = missing_glyphs(&screenplay, &ExportOptions::new()).unwrap();
+        assert!(default.is_empty());
+
+        let options = ExportOptions::new().notes(NoteStyle::Margin).cuts(true);
+        let shown = missing_glyphs(&screenplay, &options).unwrap();
+        assert_eq!(shown, ['世', '你', '好', '界']);
     }
 
-    (LazyHash::new(fontbook), fonts)
+    #[test]
+    fn scales_custom_pages() {
+        let half = PageGeometry::new(PaperSize::Custom {
+            width: 306.0,
+            height: 396.0,
+        })
+        .unwrap();
+
+        assert_eq!((half.top, half.left, half.bottom), (36.0, 54.0, 18.0));
+        assert_eq!(half.scale, 0.5);
+        assert_eq!(
+            format_page(half),
+            "width: 306.00pt, height: 396.00pt, \
+             margin: (top: 36.00pt, left: 54.00pt, right: 36.00pt, bottom: 18.00pt), scale: 0.5000"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_pages() {
+        for (width, height) in [(0.0, 396.0), (306.0, -396.0), (f64::NAN, 396.0)] {
+            let page = PageGeometry::new(PaperSize::Custom { width, height });
+            assert!(matches!(page, Err(Error::Paper { .. })));
+        }
+        let screenplay = crate::parse("INT. HOUSE - DAY");
+        let options = ExportOptions::new().paper(PaperSize::Custom {
+            width: f64::INFINITY,
+            height: 396.0,
+        });
+        assert!(matches!(
+            format_as_typst(&screenplay, &options),
+            Err(Error::Paper { .. })
+        ));
+    }
 }
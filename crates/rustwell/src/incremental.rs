@@ -0,0 +1,372 @@
+//! This module implements incremental reparsing, for editors that need to keep a
+//! [Screenplay] up to date while the source is being written.
+//!
+//! Rather than parsing the whole source again after every edit, [reparse] only parses the
+//! blocks of the source (the lines up to the next blank line) that the edit could have
+//! affected, and moves the [Span]s of all elements after them into place.
+//!
+//! # Examples
+//!
+//! ```
+//! use rustwell::ParseOptions;
+//! use rustwell::incremental::{Edit, reparse};
+//! use rustwell::screenplay::Element;
+//!
+//! let src = "INT. HOUSE - DAY\n\nThe door is open.\n\nEXT. GARDEN - NIGHT";
+//! let screenplay = rustwell::parse(src);
+//!
+//! let edit = Edit::new(30..34, "closed");
+//! let reparsed = reparse(screenplay, src, &edit, ParseOptions::default())?;
+//!
+//! assert_eq!(reparsed.changed, vec![1]);
+//! assert_eq!(
+//!     reparsed.screenplay.elements[1].node,
+//!     Element::Action("The door is closed.".into())
+//! );
+//! assert_eq!(reparsed.screenplay.elements[2].span.start.offset, 39);
+//! # Ok::<(), rustwell::Error>(())
+//! ```
+
+use std::ops::Range;
+
+use crate::{
+    diagnostic::Diagnostic,
+    error::{Error, Result},
+    events::Blocks,
+    parser::{self, ParseOptions},
+    screenplay::{Element, Screenplay},
+    span::{Position, Span, Spanned},
+};
+
+/// A change to the source, replacing the text in `range` with `replacement`.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Edit {
+    /// The byte range in the source before the edit that is replaced.
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl Edit {
+    /// Creates a new [Edit] replacing `range` with `replacement`.
+    pub fn new(range: Range<usize>, replacement: impl Into<String>) -> Self {
+        Self {
+            range,
+            replacement: replacement.into(),
+        }
+    }
+
+    /// Applies the [Edit] to `src`. Fails with [Error::Edit] if the range of the [Edit] is out
+    /// of bounds or not on [char] boundaries in `src`.
+    pub fn apply(&self, src: &str) -> Result<String> {
+        let (Some(before), Some(after)) = (
+            src.get(..self.range.start),
+            src.get(self.range.end..)
+                .filter(|_| self.range.start <= self.range.end),
+        ) else {
+            return Err(Error::Edit(self.range.clone()));
+        };
+
+        let mut out = String::with_capacity(src.len() + self.replacement.len());
+        out.push_str(before);
+        out.push_str(&self.replacement);
+        out.push_str(after);
+        Ok(out)
+    }
+}
+
+/// The result of [reparse].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Reparsed {
+    /// The [Screenplay] of the source after the edit.
+    pub screenplay: Screenplay,
+    /// The indices of the elements in the updated [Screenplay] that are new or have changed,
+    /// in order. Elements that were only moved by the edit are not included.
+    pub changed: Vec<usize>,
+    /// Whether the title page was added, removed or changed by the edit.
+    pub titlepage_changed: bool,
+    /// The byte range of the source after the edit that was parsed again. Diagnostics of the
+    /// previous source within it are replaced by [Reparsed::diagnostics], while those after it
+    /// are only moved.
+    pub range: Range<usize>,
+    /// The [Diagnostic]s of the part of the source that was parsed again.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Updates `previous`, parsed from `src` with `options`, to the source after the [Edit].
+///
+/// The result is the same as parsing the whole edited source with
+/// [parse_with_options](crate::parse_with_options), but only the blocks around the edit are
+/// parsed again. The title page is only parsed again when the first block is.
+///
+/// Fails with [Error::Edit] if the range of the [Edit] is out of bounds or not on [char]
+/// boundaries in `src`.
+pub fn reparse(
+    previous: Screenplay,
+    src: &str,
+    edit: &Edit,
+    options: ParseOptions,
+) -> Result<Reparsed> {
+    let new_src = edit.apply(src)?;
+    let old_starts = Blocks::starts(src);
+    let new_starts = Blocks::starts(&new_src);
+    let edit_end = edit.range.start + edit.replacement.len();
+    let delta = edit_end as isize - edit.range.end as isize;
+
+    let Screenplay {
+        mut titlepage,
+        mut elements,
+    } = previous;
+
+    // Everything before the edited block is the same, but the block before it is parsed again
+    // as well, in case the edit turned a dialogue into dual dialogue with it
+    let edited = old_starts.partition_point(|&start| start <= edit.range.start) - 1;
+    let mut first = edited.saturating_sub(1);
+    while first > 0 && straddles(&elements, old_starts[first]) {
+        first -= 1;
+    }
+    let start = old_starts[first];
+
+    // After the edit, the blocks are the same again as soon as a block starts at the same place
+    // in both sources. The block after that is parsed again as well, for the same reason.
+    let synced = new_starts
+        .iter()
+        .enumerate()
+        .skip_while(|&(_, &start)| start < edit_end)
+        .find(|&(_, &start)| {
+            old_starts
+                .binary_search(&((start as isize - delta) as usize))
+                .is_ok()
+        })
+        .map(|(i, _)| i);
+    let mut last = synced.map_or(new_starts.len(), |i| i + 1);
+    while last < new_starts.len()
+        && straddles(&elements, (new_starts[last] as isize - delta) as usize)
+    {
+        last += 1;
+    }
+    let new_end = new_starts.get(last).copied().unwrap_or(new_src.len());
+    let old_end = (new_end as isize - delta) as usize;
+
+    let region_start = Position {
+        offset: start,
+        line: new_src[..start].matches('\n').count() + 1,
+        column: 1,
+    };
+    let (reparsed, diagnostics) =
+        parser::parse_part(&new_src[start..new_end], options, region_start, None, false);
+    let mut titlepage_changed = false;
+    if start == 0 {
+        titlepage_changed = titlepage != reparsed.titlepage;
+        titlepage = reparsed.titlepage;
+    }
+
+    let lines = |s: &str| s.matches('\n').count() as isize;
+    let line_delta = lines(&new_src[start..new_end]) - lines(&src[start..old_end]);
+    let from = elements.partition_point(|e| e.span.start.offset < start);
+    let to = elements.partition_point(|e| e.span.start.offset < old_end);
+    for element in &mut elements[to..] {
+        shift_element(element, delta, line_delta);
+    }
+
+    // Only report the elements that differ from the ones they replaced
+    let old = &elements[from..to];
+    let new = &reparsed.elements;
+    let same = |a: &Spanned<Element>, b: &Spanned<Element>| a == b && a.span == b.span;
+    let prefix = old.iter().zip(new).take_while(|(a, b)| same(a, b)).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| {
+            let mut a = (*a).clone();
+            shift_element(&mut a, delta, line_delta);
+            same(&a, b)
+        })
+        .count();
+    let changed = (from + prefix..from + new.len() - suffix).collect();
+
+    elements.splice(from..to, reparsed.elements);
+
+    Ok(Reparsed {
+        screenplay: Screenplay {
+            titlepage,
+            elements,
+        },
+        changed,
+        titlepage_changed,
+        range: start..new_end,
+        diagnostics,
+    })
+}
+
+/// If an element starts before `offset` and ends after it.
+fn straddles(elements: &[Spanned<Element>], offset: usize) -> bool {
+    let after = elements.partition_point(|e| e.span.start.offset < offset);
+    after > 0 && elements[after - 1].span.end.offset > offset
+}
+
+/// Moves the [Span]s of an element, and all parts of it, by `delta` bytes and `line_delta`
+/// lines.
+fn shift_element(element: &mut Spanned<Element>, delta: isize, line_delta: isize) {
+    shift(&mut element.span, delta, line_delta);
+    match &mut element.node {
+        Element::Dialogue(dialogue) => {
+            for e in &mut dialogue.elements {
+                shift(&mut e.span, delta, line_delta);
+            }
+        }
+        Element::DualDialogue(dialogue1, dialogue2) => {
+            for dialogue in [dialogue1, dialogue2] {
+                shift(&mut dialogue.span, delta, line_delta);
+                for e in &mut dialogue.elements {
+                    shift(&mut e.span, delta, line_delta);
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+fn shift(span: &mut Span, delta: isize, line_delta: isize) {
+    for position in [&mut span.start, &mut span.end] {
+        position.offset = (position.offset as isize + delta) as usize;
+        position.line = (position.line as isize + line_delta) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every [Span] of a [Screenplay], to also compare the positions of all parts of it.
+    fn spans(screenplay: &Screenplay) -> Vec<Span> {
+        let mut spans = Vec::new();
+        spans.extend(screenplay.titlepage.as_ref().map(|t| t.span));
+        for element in &screenplay.elements {
+            spans.push(element.span);
+            let dialogues = match &element.node {
+                Element::Dialogue(dialogue) => vec![dialogue],
+                Element::DualDialogue(dialogue1, dialogue2) => {
+                    spans.push(dialogue1.span);
+                    spans.push(dialogue2.span);
+                    vec![&dialogue1.node, &dialogue2.node]
+                }
+                _ => vec![],
+            };
+            for dialogue in dialogues {
+                spans.extend(dialogue.elements.iter().map(|e| e.span));
+            }
+        }
+        spans
+    }
+
+    /// Reparses `src` after `edit`, checking that the result is the same as parsing the edited
+    /// source as a whole, and returns the changed indices.
+    fn reparse_tester(src: &str, edit: Edit) -> Vec<usize> {
+        reparse_checked(src, edit).changed
+    }
+
+    /// Like [reparse_tester], but also checks the diagnostics and returns the whole [Reparsed].
+    fn reparse_checked(src: &str, edit: Edit) -> Reparsed {
+        let options = ParseOptions::default();
+        let reparsed = reparse(parser::parse(src), src, &edit, options).unwrap();
+        let new_src = edit.apply(src).unwrap();
+        let (parsed, diagnostics) = parser::parse_with_diagnostics(&new_src, options);
+
+        assert_eq!(reparsed.screenplay, parsed);
+        assert_eq!(spans(&reparsed.screenplay), spans(&parsed));
+        let within = |d: &&Diagnostic| reparsed.range.contains(&d.span.start.offset);
+        let expected: Vec<_> = diagnostics.iter().filter(within).cloned().collect();
+        assert_eq!(reparsed.diagnostics, expected);
+        reparsed
+    }
+
+    const SRC: &str = "Title: Test\n\nINT. HOUSE - DAY\n\nThe door is open.\n\n\
+                       BOB\nHello?\n\nALICE\nHi.\n\nEXT. GARDEN - NIGHT\n\nIt is dark.";
+
+    #[test]
+    fn reparses_edited_element() {
+        let at = SRC.find("open").unwrap();
+        let changed = reparse_tester(SRC, Edit::new(at..at + 4, "wide\nopen"));
+
+        assert_eq!(changed, vec![1]);
+    }
+
+    #[test]
+    fn reparses_into_dual_dialogue() {
+        let at = SRC.find("ALICE").unwrap() + 5;
+        let changed = reparse_tester(SRC, Edit::new(at..at, " ^"));
+
+        assert_eq!(changed, vec![2]);
+    }
+
+    #[test]
+    fn reparses_out_of_dual_dialogue() {
+        let src = SRC.replace("ALICE", "ALICE ^");
+        let at = src.find(" ^").unwrap();
+        let changed = reparse_tester(&src, Edit::new(at..at + 2, ""));
+
+        assert_eq!(changed, vec![2, 3]);
+    }
+
+    #[test]
+    fn reparses_joined_blocks() {
+        let at = SRC.find("open.").unwrap() + 5;
+        let changed = reparse_tester(SRC, Edit::new(at..at + 1, ""));
+
+        assert_eq!(changed, vec![1]);
+    }
+
+    #[test]
+    fn reparses_unterminated_boneyard() {
+        let at = SRC.find("BOB").unwrap();
+        let edit = Edit::new(at..at, "/* ");
+        let changed = reparse_tester(SRC, edit.clone());
+        assert_eq!(changed, Vec::<usize>::new());
+
+        let src = edit.apply(SRC).unwrap();
+        let at = src.find("It is").unwrap();
+        let changed = reparse_tester(&src, Edit::new(at..at, "*/"));
+        assert_eq!(changed, vec![2]);
+    }
+
+    #[test]
+    fn reparses_titlepage() {
+        let reparsed = reparse_checked(SRC, Edit::new(7..11, "Another"));
+        assert_eq!(reparsed.changed, Vec::<usize>::new());
+        assert!(reparsed.titlepage_changed);
+
+        let at = SRC.find("open").unwrap();
+        let reparsed = reparse_checked(SRC, Edit::new(at..at + 4, "shut"));
+        assert!(!reparsed.titlepage_changed);
+    }
+
+    #[test]
+    fn reports_diagnostics_of_reparsed_blocks() {
+        let at = SRC.find("It is").unwrap();
+        let reparsed = reparse_checked(SRC, Edit::new(at..at, "[[unclosed\n\n"));
+
+        assert_eq!(reparsed.diagnostics.len(), 1);
+        assert_eq!(reparsed.diagnostics[0].span.start.offset, at);
+    }
+
+    #[test]
+    fn fails_on_invalid_edits() {
+        let screenplay = parser::parse("Café");
+        let options = ParseOptions::default();
+
+        for (start, end) in [(2, 10), (4, 4), (3, 2)] {
+            let edit = Edit::new(start..end, "x");
+            let reparsed = reparse(screenplay.clone(), "Café", &edit, options);
+            assert!(matches!(reparsed, Err(Error::Edit(r)) if r == (start..end)));
+        }
+    }
+
+    #[test]
+    fn moves_elements_after_edit() {
+        let at = SRC.find("The door").unwrap();
+        let changed = reparse_tester(SRC, Edit::new(at..at, "A new action.\n\n"));
+
+        assert_eq!(changed, vec![1]);
+    }
+}
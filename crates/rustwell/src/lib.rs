@@ -12,9 +12,9 @@
 //! use std::io::BufWriter;
 //! use std::io::stdout;
 //! use rustwell::rich_string::RichString;
-//! use rustwell::{export_html, parse};
+//! use rustwell::{ExportOptions, export_html, parse};
 //!
-//! fn main() {
+//! fn main() -> rustwell::Result<()> {
 //!     let script = r#"
 //!     Title: Example Screenplay
 //!
@@ -30,22 +30,38 @@
 //!     let parsed = parse(script);
 //!     let mut output = BufWriter::new(stdout());
 //!
-//!     export_html(&parsed, &mut output, false, false);
+//!     export_html(&parsed, &mut output, &ExportOptions::new())?;
+//!     Ok(())
 //! }
 //! ```
 
 use std::io::Read;
 
+mod error;
 mod export;
 mod parser;
 
+pub mod diagnostic;
+pub mod events;
+pub mod incremental;
 pub mod rich_string;
 pub mod screenplay;
+pub mod span;
+pub mod stats;
 pub use screenplay::Screenplay;
 
+pub use error::{Error, Result};
+pub use export::FALLBACK_FONTS;
+pub use export::export_fountain;
 pub use export::export_html;
 pub use export::export_pdf;
 pub use export::export_typst;
+pub use export::exporters;
+pub use export::layout::{HeadingPosition, Layout, PagePosition, format_eighths};
+pub use export::missing_glyphs;
+pub use export::{ExportOptions, Exporter, NoteStyle, PaperSize, Theme};
+pub use export::{FountainExporter, HtmlExporter, PdfExporter, TypstExporter};
+pub use parser::ParseOptions;
 
 /// Parses a Fountain source string into a [Screenplay] structure.
 ///
@@ -71,9 +87,51 @@ pub fn parse(src: impl AsRef<str>) -> Screenplay {
     parser::parse(src.as_ref())
 }
 
+/// Parses a Fountain source string into a [Screenplay] structure, like [parse], with
+/// [ParseOptions] deciding what is kept of the source.
+///
+/// # Examples
+///
+/// ```
+/// use rustwell::{ParseOptions, parse_with_options, screenplay::Element};
+///
+/// let options = ParseOptions { boneyards: true, ..Default::default() };
+/// let screenplay = parse_with_options("/* Cut scene */\n\nA man stands alone.", options);
+/// assert_eq!(screenplay.elements[0].node, Element::Boneyard(" Cut scene ".to_string()));
+/// ```
+pub fn parse_with_options(src: impl AsRef<str>, options: ParseOptions) -> Screenplay {
+    parser::parse_with_options(src.as_ref(), options)
+}
+
+/// Parses a Fountain source string into a [Screenplay] structure, like [parse_with_options],
+/// along with [Diagnostic](diagnostic::Diagnostic)s for suspicious or malformed parts of the
+/// source, such as unterminated boneyards or unclosed notes.
+///
+/// # Examples
+///
+/// ```
+/// use rustwell::{ParseOptions, parse_with_diagnostics};
+///
+/// let (screenplay, diagnostics) =
+///     parse_with_diagnostics("ALICE ^\nHello?", ParseOptions::default());
+///
+/// assert_eq!(screenplay.elements.len(), 1);
+/// assert_eq!(diagnostics[0].span.range(), 6..7);
+/// ```
+pub fn parse_with_diagnostics(
+    src: impl AsRef<str>,
+    options: ParseOptions,
+) -> (Screenplay, Vec<diagnostic::Diagnostic>) {
+    parser::parse_with_diagnostics(src.as_ref(), options)
+}
+
 /// Parses a Fountain source file into a [Screenplay] structure.
-pub fn parse_reader(mut r: impl Read) -> Screenplay {
-    let mut src = String::new();
-    r.read_to_string(&mut src).expect("Failed to read data");
-    parser::parse(&src)
+///
+/// Fails with [Error::Io] if the reader could not be read, and with [Error::InvalidUtf8] if
+/// the source is not valid UTF-8.
+pub fn parse_reader(mut r: impl Read) -> Result<Screenplay> {
+    let mut src = Vec::new();
+    r.read_to_end(&mut src)?;
+    let src = String::from_utf8(src)?;
+    Ok(parser::parse(&src))
 }
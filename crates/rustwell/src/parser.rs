@@ -1,16 +1,26 @@
+use crate::diagnostic::Diagnostic;
+use crate::rich_string;
 use crate::rich_string::RichString;
 use crate::screenplay::Dialogue;
 use crate::screenplay::DialogueElement;
 use crate::screenplay::Element;
+use crate::screenplay::SceneHeading;
 use crate::screenplay::Screenplay;
+use crate::screenplay::Setting;
 use crate::screenplay::TitlePage;
+use crate::span::Position;
+use crate::span::Span;
+use crate::span::Spanned;
 use std::iter::Peekable;
+use std::ops::Range;
 use std::str::Lines;
+use std::vec;
 
 /// Parses a Fountain source string into a [`Screenplay`] structure.
 ///
 /// Preprocesses the source text by removing
-/// boneyards, notes and normalizing tabs to spaces.
+/// boneyards, notes and normalizing tabs to spaces. The notes are
+/// then put back into the elements they were written in.
 ///
 /// # Examples
 ///
@@ -29,46 +39,141 @@ use std::str::Lines;
 /// ```
 #[must_use]
 pub fn parse(src: &str) -> Screenplay {
-    let cleaned = preprocess_source(src);
-    Parser::new(&cleaned).parse()
+    parse_with_options(src, ParseOptions::default())
+}
+
+/// Parses a Fountain source string into a [`Screenplay`] structure, like [`parse`], with
+/// [`ParseOptions`] deciding what is kept of the source.
+#[must_use]
+pub fn parse_with_options(src: &str, options: ParseOptions) -> Screenplay {
+    parse_with_diagnostics(src, options).0
+}
+
+/// Parses a Fountain source string into a [`Screenplay`] structure, like [`parse_with_options`],
+/// while also returning [`Diagnostic`]s for anything suspicious or malformed, ordered by where
+/// they appear in the source.
+#[must_use]
+pub fn parse_with_diagnostics(src: &str, options: ParseOptions) -> (Screenplay, Vec<Diagnostic>) {
+    let start = Position {
+        offset: 0,
+        line: 1,
+        column: 1,
+    };
+    parse_part(src, options, start, None, false)
+}
+
+/// Parses a part of a larger source, made up of whole blocks, where `start` is the [`Position`]
+/// in the larger source the part starts at. The title page is only parsed at the very start of
+/// the source.
+///
+/// The `previous` [`Element`] of the source is pushed before the elements of the part, so that
+/// a dialogue can become dual dialogue with the first dialogue of the part. If the part starts
+/// `within_boneyard`, meaning within a boneyard opened in an earlier part, everything up to the
+/// first `*/` is cut, and that boneyard is not warned about if it is never closed.
+pub(crate) fn parse_part(
+    src: &str,
+    options: ParseOptions,
+    start: Position,
+    previous: Option<Spanned<Element>>,
+    within_boneyard: bool,
+) -> (Screenplay, Vec<Diagnostic>) {
+    let mut preprocessed = preprocess_source(src, within_boneyard);
+    if !options.boneyards {
+        preprocessed
+            .trivia
+            .retain(|t| t.kind != TriviaKind::Boneyard);
+    }
+
+    let map = SourceMap::new(src, preprocessed.offsets, start);
+    let mut parser = Parser::new(&preprocessed.source, &map, preprocessed.trivia);
+    parser.caseless_cues = options.caseless_cues;
+    parser.elements.extend(previous);
+    if start.offset == 0 {
+        parser.parse_title();
+    }
+    let (screenplay, mut diagnostics) = parser.parse();
+
+    diagnostics.extend(
+        preprocessed
+            .warnings
+            .into_iter()
+            .map(|(message, range)| Diagnostic::warning(message, map.original_span(range))),
+    );
+    diagnostics.sort_by_key(|d| d.span.start);
+
+    (screenplay, diagnostics)
+}
+
+/// Options for what is kept of the source when parsing.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// Keep boneyards (`/* cut */`) as [`Element::Boneyard`]s, or within the text of the
+    /// element they were written in, instead of removing them.
+    pub boneyards: bool,
+    /// Read a short line without case or sentence punctuation, followed by a line, as a
+    /// character cue, like `田中` in scripts without upper and lower case such as Japanese,
+    /// Hebrew or Arabic. Such a line may just as well be a short line of action, which then has
+    /// to be forced with `!`, so cues in those scripts have to be forced with `@` otherwise.
+    pub caseless_cues: bool,
 }
 
 /// Internal parser state machine for Fountain.
 ///
 /// Keeps an iterator of the source, a accumulative list of [`Element`]s, and
-/// a state. Also tracks a [`TitlePage`] if such exists in the source.
+/// a state. Also tracks a [`TitlePage`] if such exists in the source, and the
+/// [`Trivia`] that is yet to be put back into the elements.
 struct Parser<'a> {
+    src: &'a str,
+    map: &'a SourceMap<'a>,
     lines: Peekable<Lines<'a>>,
+    trivia: Peekable<vec::IntoIter<Trivia>>,
+    /// The [`Span`] of the line currently being parsed.
+    line: Span,
+    /// The offsets in `src` where the trimmed and the whole current line end.
+    line_end: (usize, usize),
     state: State,
-    elements: Vec<Element>,
-    title_page: Option<TitlePage>,
+    elements: Vec<Spanned<Element>>,
+    title_page: Option<Spanned<TitlePage>>,
+    diagnostics: Vec<Diagnostic>,
+    /// See [`ParseOptions::caseless_cues`].
+    caseless_cues: bool,
 }
 
 impl<'a> Parser<'a> {
     /// Create new parser
     ///
-    /// Expects `src` to have been preprocessed.
-    fn new(src: &'a str) -> Self {
+    /// Expects `src` to have been preprocessed, with `map` leading back to the original source.
+    fn new(src: &'a str, map: &'a SourceMap<'a>, trivia: Vec<Trivia>) -> Self {
         Self {
+            src,
+            map,
             lines: src.lines().peekable(),
+            trivia: trivia.into_iter().peekable(),
+            line: Span::default(),
+            line_end: (0, 0),
             state: State::Default,
             elements: Vec::new(),
             title_page: None,
+            diagnostics: Vec::new(),
+            caseless_cues: false,
         }
     }
 
     /// Main entry point for parser
     ///
-    /// Starts by parsing a potential title. Before moving on to the main loop.
+    /// Expects a potential title to already have been parsed with [`Parser::parse_title`].
     /// A line with two or more spaces is always treated as intentional empty lines.
     ///
     /// Might seem like trimming is used a lot. The intention is that the
     /// try functions work without having trimmed. Cost is extremely low when
     /// calling trim on a already trimmed [&str].
-    fn parse(mut self) -> Screenplay {
-        self.parse_title();
+    fn parse(mut self) -> (Screenplay, Vec<Diagnostic>) {
         while let Some(line) = self.lines.next() {
+            // Trivia left behind on earlier lines, not belonging to any element
+            self.push_trivia(self.offset_of(line));
+
             let trimmed = line.trim();
+            self.set_line(line);
 
             if trimmed.is_empty() && !line.starts_with("  ") {
                 self.state = State::Default;
@@ -91,20 +196,19 @@ impl<'a> Parser<'a> {
                     {}
                 }
                 State::InDialogue => {
+                    let element = if trimmed.starts_with('(') {
+                        DialogueElement::Parenthetical(self.rich(trimmed))
+                    } else {
+                        DialogueElement::Line(self.rich(trimmed))
+                    };
+
+                    self.extend_last();
+                    let line = self.line;
                     let curr_dialogue = self
                         .get_last_dialogue()
                         .expect("Must exist since we are in dialogue block");
 
-                    if trimmed.starts_with('(') {
-                        curr_dialogue
-                            .elements
-                            .push(DialogueElement::Parenthetical(RichString::from(trimmed)));
-                        continue;
-                    }
-
-                    curr_dialogue
-                        .elements
-                        .push(DialogueElement::Line(RichString::from(trimmed)));
+                    curr_dialogue.elements.push(Spanned::new(element, line));
                 }
                 State::InBlock => {
                     if self.try_centered(trimmed)
@@ -113,9 +217,101 @@ impl<'a> Parser<'a> {
                     {}
                 }
             }
+
+            self.attach_trivia();
+        }
+        self.push_trivia(usize::MAX);
+
+        let screenplay = Screenplay {
+            titlepage: self.title_page,
+            elements: self.elements,
+        };
+        (screenplay, self.diagnostics)
+    }
+
+    /// The offset in the preprocessed source of a slice of it.
+    fn offset_of(&self, s: &str) -> usize {
+        s.as_ptr() as usize - self.src.as_ptr() as usize
+    }
+
+    /// The [`Span`] in the original source of a slice of the preprocessed source.
+    fn span_of(&self, s: &str) -> Span {
+        let start = self.offset_of(s);
+        self.map.span(start, start + s.len())
+    }
+
+    /// Makes `line` the current line.
+    fn set_line(&mut self, line: &str) {
+        let trimmed = line.trim();
+        self.line = self.span_of(trimmed);
+        self.line_end = (
+            self.offset_of(trimmed) + trimmed.len(),
+            self.offset_of(line) + line.len(),
+        );
+    }
+
+    /// Creates a [`RichString`] from a slice of the current line, putting back the [`Trivia`]
+    /// that was written within it. A slice reaching the end of the line also takes the
+    /// [`Trivia`] after it.
+    fn rich(&mut self, s: &str) -> RichString {
+        let start = self.offset_of(s);
+        let end = start + s.len();
+        let (text_end, line_end) = self.line_end;
+        let until = if end >= text_end { line_end } else { end };
+
+        let mut trivia = Vec::new();
+        while let Some(t) = self.trivia.next_if(|t| t.anchor <= until) {
+            trivia.push((t.anchor.clamp(start, end) - start, t.into_rich_element()));
+        }
+
+        let mut rs = RichString::new();
+        rs.push_str_with_trivia(s, trivia);
+        rs
+    }
+
+    /// Attaches the [`Trivia`] of the current line that was not written within any text, to the
+    /// last text of the last [`Element`].
+    fn attach_trivia(&mut self) {
+        let (_, line_end) = self.line_end;
+        while let Some(t) = self.trivia.next_if(|t| t.anchor <= line_end) {
+            let Some(rs) = self.elements.last_mut().and_then(|e| last_rich_string(e)) else {
+                self.push_single_trivia(t);
+                continue;
+            };
+
+            rs.push_trivia(t.into_rich_element());
         }
+    }
+
+    /// Pushes all [`Trivia`] before `until` as [`Element`]s of their own.
+    fn push_trivia(&mut self, until: usize) {
+        while let Some(t) = self.trivia.next_if(|t| t.anchor < until) {
+            self.push_single_trivia(t);
+        }
+    }
 
-        Screenplay::new(self.title_page, self.elements)
+    fn push_single_trivia(&mut self, trivia: Trivia) {
+        let span = self.map.original_span(trivia.range.clone());
+        self.elements
+            .push(Spanned::new(trivia.into_element(), span));
+    }
+
+    /// Pushes a new [`Element`] spanning the current line.
+    fn push(&mut self, element: Element) {
+        self.elements.push(Spanned::new(element, self.line));
+    }
+
+    /// Extends the [`Span`] of the last [`Element`] to cover the current line as well.
+    fn extend_last(&mut self) {
+        let line = self.line;
+        let Some(last) = self.elements.last_mut() else {
+            return;
+        };
+
+        last.span = last.span.to(line);
+        if let Element::DualDialogue(_, second) = &mut last.node {
+            second.span = second.span.to(line);
+        }
     }
 
     /// `try_` is a helper function taking a predicate and a handle function
@@ -136,8 +332,14 @@ impl<'a> Parser<'a> {
     fn try_section(&mut self, line: &str) -> bool {
         self.try_(
             line,
-            |_, s| s.trim_start().starts_with("#").then_some(s),
-            |_, _| return,
+            |_, s| s.trim_start().starts_with("#").then_some(s.trim_start()),
+            |this, inner| {
+                let title = inner.trim_start_matches('#');
+                let depth = inner.len() - title.len();
+
+                let title = this.rich(title.trim());
+                this.push(Element::Section { depth, title });
+            },
         )
     }
 
@@ -146,16 +348,18 @@ impl<'a> Parser<'a> {
             line,
             |_, s| s.trim_start().strip_prefix('='),
             |this, inner| {
+                let rs = this.rich(inner);
                 if this.state == State::InBlock
-                    && let Some(Element::Synopsis(rs)) = this.elements.last_mut()
+                    && let Some(Element::Synopsis(last)) =
+                        this.elements.last_mut().map(|e| &mut e.node)
                 {
-                    rs.push_str("\n");
-                    rs.push_str(inner);
+                    last.push_str("\n");
+                    last.append(rs);
+                    this.extend_last();
                     return;
                 }
 
-                let rs = RichString::from(inner);
-                this.elements.push(Element::Synopsis(rs));
+                this.push(Element::Synopsis(rs));
 
                 this.state = State::InBlock;
             },
@@ -166,7 +370,7 @@ impl<'a> Parser<'a> {
         self.try_(
             line,
             |_, s| s.trim_start().starts_with("===").then_some(s),
-            |this, _| this.elements.push(Element::PageBreak),
+            |this, _| this.push(Element::PageBreak),
         )
     }
 
@@ -175,7 +379,8 @@ impl<'a> Parser<'a> {
             line,
             |_, s| s.trim_start().strip_prefix('!'),
             |this, inner| {
-                this.elements.push(Element::Action(RichString::from(inner)));
+                let rs = this.rich(inner);
+                this.push(Element::Action(rs));
                 this.state = State::InBlock;
             },
         )
@@ -187,16 +392,18 @@ impl<'a> Parser<'a> {
             |_, s| s.trim().strip_prefix('>').and_then(|u| u.strip_suffix('<')),
             |this, inner| {
                 let inner = inner.trim();
+                let rs = this.rich(inner);
                 if this.state == State::InBlock
-                    && let Some(Element::CenteredText(rs)) = this.elements.last_mut()
+                    && let Some(Element::CenteredText(last)) =
+                        this.elements.last_mut().map(|e| &mut e.node)
                 {
-                    rs.push_str("\n");
-                    rs.push_str(inner);
+                    last.push_str("\n");
+                    last.append(rs);
+                    this.extend_last();
                     return;
                 }
 
-                let rs = RichString::from(inner);
-                this.elements.push(Element::CenteredText(rs));
+                this.push(Element::CenteredText(rs));
 
                 this.state = State::InBlock;
             },
@@ -208,16 +415,18 @@ impl<'a> Parser<'a> {
             line,
             |_, s| s.trim_start().strip_prefix('~'),
             |this, inner| {
+                let rs = this.rich(inner);
                 if this.state == State::InBlock
-                    && let Some(Element::Lyrics(rs)) = this.elements.last_mut()
+                    && let Some(Element::Lyrics(last)) =
+                        this.elements.last_mut().map(|e| &mut e.node)
                 {
-                    rs.push_str("\n");
-                    rs.push_str(inner);
+                    last.push_str("\n");
+                    last.append(rs);
+                    this.extend_last();
                     return;
                 }
 
-                let rs = RichString::from(inner);
-                this.elements.push(Element::Lyrics(rs));
+                this.push(Element::Lyrics(rs));
 
                 this.state = State::InBlock;
             },
@@ -229,16 +438,18 @@ impl<'a> Parser<'a> {
             line,
             |_, line| Some(line),
             |this, inner| {
+                let rs = this.rich(inner);
                 if this.state == State::InBlock
-                    && let Some(Element::Action(rs)) = this.elements.last_mut()
+                    && let Some(Element::Action(last)) =
+                        this.elements.last_mut().map(|e| &mut e.node)
                 {
-                    rs.push_str("\n");
-                    rs.push_str(inner);
+                    last.push_str("\n");
+                    last.append(rs);
+                    this.extend_last();
                     return;
                 }
 
-                let rs = RichString::from(inner);
-                this.elements.push(Element::Action(rs));
+                this.push(Element::Action(rs));
 
                 this.state = State::InBlock;
             },
@@ -292,10 +503,8 @@ impl<'a> Parser<'a> {
                     inner = new_inner.trim_end();
                 }
 
-                this.elements.push(Element::Heading {
-                    slug: RichString::from(inner),
-                    number,
-                });
+                let slug = this.rich(inner);
+                this.push(Element::Heading { slug, number });
 
                 this.lines.next();
             },
@@ -304,7 +513,13 @@ impl<'a> Parser<'a> {
 
     fn get_last_dialogue(&mut self) -> Option<&mut Dialogue> {
         let (Some(Element::Dialogue(curr_dialogue))
-        | Some(Element::DualDialogue(_, curr_dialogue))) = self.elements.last_mut()
+        | Some(Element::DualDialogue(
+            _,
+            Spanned {
+                node: curr_dialogue,
+                ..
+            },
+        ))) = self.elements.last_mut().map(|e| &mut e.node)
         else {
             return None;
         };
@@ -315,15 +530,37 @@ impl<'a> Parser<'a> {
     fn insert_empty_dialogue<'s>(&mut self, inner: &'s str) -> &'s str {
         let new_dialogue = Dialogue::new();
 
+        // An escaped `^`, like in `ALICE \^`, is part of the name
         if let Some(stripped) = inner.trim_end().strip_suffix('^')
-            && let Some(&Element::Dialogue(_)) = self.elements.last()
-            && let Some(Element::Dialogue(d)) = self.elements.pop()
+            && stripped
+                .chars()
+                .rev()
+                .take_while(|&c| c == '\\')
+                .count()
+                .is_multiple_of(2)
         {
-            self.elements.push(Element::DualDialogue(d, new_dialogue));
-            return stripped;
+            if let Some(Element::Dialogue(_)) = self.elements.last().map(|e| &e.node)
+                && let Some(Spanned {
+                    node: Element::Dialogue(d),
+                    span,
+                }) = self.elements.pop()
+            {
+                let dual = Element::DualDialogue(
+                    Spanned::new(d, span),
+                    Spanned::new(new_dialogue, self.line),
+                );
+                self.elements.push(Spanned::new(dual, span.to(self.line)));
+                return stripped.trim_end();
+            }
+
+            let caret = self.offset_of(stripped) + stripped.len();
+            self.diagnostics.push(Diagnostic::warning(
+                "dual dialogue caret `^` without any dialogue before it",
+                self.map.span(caret, caret + 1),
+            ));
         }
 
-        self.elements.push(Element::Dialogue(new_dialogue));
+        self.push(Element::Dialogue(new_dialogue));
         inner
     }
 
@@ -337,25 +574,27 @@ impl<'a> Parser<'a> {
                 }
 
                 let head = trimmed.split_once('(').map_or(trimmed, |(h, _)| h);
-                let has_alpha = head.chars().any(char::is_alphabetic);
-                let has_lower = head.chars().any(char::is_lowercase);
-                (has_alpha && !has_lower && !this.next_line_is_empty()).then_some(trimmed)
+                (is_cue(head, this.caseless_cues) && !this.next_line_is_empty()).then_some(trimmed)
             },
             |this, inner| {
                 let mut inner = this.insert_empty_dialogue(inner);
-
-                let curr_dialogue = this
-                    .get_last_dialogue()
-                    .expect("Just pushed to list, must exist");
+                let mut extension = None;
 
                 if let Some((head, tail)) = inner.split_once('(')
-                    && let Some((extension, _)) = tail.split_once(')')
+                    && let Some((ext, _)) = tail.split_once(')')
                 {
-                    curr_dialogue.extension = Some(RichString::from(extension));
                     inner = head.trim_end();
+                    extension = Some(ext);
                 }
 
-                curr_dialogue.character = RichString::from(inner);
+                let character = this.rich(inner);
+                let extension = extension.map(|ext| this.rich(ext));
+
+                let curr_dialogue = this
+                    .get_last_dialogue()
+                    .expect("Just pushed to list, must exist");
+                curr_dialogue.character = character;
+                curr_dialogue.extension = extension;
 
                 this.state = State::InDialogue;
             },
@@ -379,8 +618,8 @@ impl<'a> Parser<'a> {
                 (transition_elem && this.next_line_is_empty()).then_some(line)
             },
             |this, inner| {
-                this.elements
-                    .push(Element::Transition(RichString::from(inner)));
+                let rs = this.rich(inner);
+                this.push(Element::Transition(rs));
 
                 this.lines.next();
             },
@@ -389,29 +628,27 @@ impl<'a> Parser<'a> {
 
     fn parse_title(&mut self) {
         let mut tp = TitlePage::new();
+        let mut span = None;
 
-        while let Some(line) = self.lines.peek() {
+        while let Some(&line) = self.lines.peek() {
             let Some((key, val)) = line.split_once(':') else {
                 break;
             };
             self.lines.next(); // Consume the key line
+            self.set_line(line);
+            let start = *span.get_or_insert(self.line);
 
             let mut values = Vec::new();
 
             if val.trim().is_empty() {
                 values = self.take_indented_block();
             } else {
-                values.push(RichString::from(val));
+                values.push(self.rich(val.trim()));
             }
+            span = Some(start.to(self.line));
 
-            match key.trim().to_ascii_uppercase().as_str() {
-                "TITLE" => tp.title = values,
-                "CREDIT" => tp.credit = values,
-                "AUTHOR" | "AUTHORS" => tp.authors = values,
-                "SOURCE" => tp.source = values,
-                "DRAFT DATE" => tp.draft_date = values,
-                "CONTACT" => tp.contact = values,
-                _ => (),
+            if !values.is_empty() {
+                tp.insert(key, values);
             }
         }
 
@@ -419,23 +656,20 @@ impl<'a> Parser<'a> {
             self.lines.next();
         }
 
-        if !tp.title.is_empty()
-            || !tp.credit.is_empty()
-            || !tp.authors.is_empty()
-            || !tp.source.is_empty()
-            || !tp.draft_date.is_empty()
-            || !tp.contact.is_empty()
-        {
-            self.title_page = Some(tp);
+        if !tp.is_empty() {
+            self.title_page = Some(Spanned::new(tp, span.unwrap_or_default()));
         }
     }
 
+    /// Takes all following indented lines, leaving the [`Span`] of the last one as the current
+    /// line.
     fn take_indented_block(&mut self) -> Vec<RichString> {
         let mut out = Vec::new();
         while let Some(next) = self.lines.peek().copied() {
             if next.starts_with("   ") {
                 self.lines.next();
-                out.push(RichString::from(next.trim()));
+                self.set_line(next);
+                out.push(self.rich(next.trim()));
             } else {
                 break;
             }
@@ -448,12 +682,265 @@ impl<'a> Parser<'a> {
     }
 }
 
-/// Removes boneyards, notes and normalizes tabs to four spaces
-fn preprocess_source(src: &str) -> String {
+/// The most characters a character cue in a script without case can have.
+const MAX_CASELESS_CUE: usize = 16;
+
+/// Punctuation ending or splitting sentences, which is never part of a character cue in a script
+/// without case.
+const SENTENCE_PUNCTUATION: [char; 22] = [
+    '.', ',', '!', '?', ':', ';', '…', '。', '、', '，', '．', '！', '？', '：', '；', '「', '」',
+    '『', '』', '،', '؛', '؟',
+];
+
+/// Whether the name part of a line, before any extension, reads as a character cue, meaning it
+/// is written in upper case.
+///
+/// Scripts without case, like Japanese, Chinese, Korean, Hebrew or Arabic, have no upper case to
+/// tell a name from a short line of action. With `caseless` set, a short name without sentence
+/// punctuation in such a script is taken as a cue, judged from the line alone.
+fn is_cue(head: &str, caseless: bool) -> bool {
+    let has_lower = head.chars().any(char::is_lowercase);
+    if has_lower || head.chars().any(char::is_uppercase) {
+        return !has_lower;
+    }
+
+    caseless
+        && head.chars().any(char::is_alphabetic)
+        && head.trim().chars().count() <= MAX_CASELESS_CUE
+        && !head.contains(SENTENCE_PUNCTUATION)
+}
+
+/// The prefixes of scene headings, longest first so that `INT./EXT.` is not taken as `INT.`.
+const SETTINGS: [(&str, Setting); 9] = [
+    ("INT./EXT", Setting::InteriorExterior),
+    ("INT/EXT", Setting::InteriorExterior),
+    ("EXT./INT", Setting::ExteriorInterior),
+    ("EXT/INT", Setting::ExteriorInterior),
+    ("I/E", Setting::InteriorExterior),
+    ("E/I", Setting::ExteriorInterior),
+    ("INT", Setting::Interior),
+    ("EXT", Setting::Exterior),
+    ("EST", Setting::Establishing),
+];
+
+/// Words that mark the last part of a slug as the time of day.
+const TIMES_OF_DAY: [&str; 15] = [
+    "DAY",
+    "NIGHT",
+    "MORNING",
+    "AFTERNOON",
+    "EVENING",
+    "DAWN",
+    "DUSK",
+    "SUNRISE",
+    "SUNSET",
+    "NOON",
+    "MIDNIGHT",
+    "CONTINUOUS",
+    "LATER",
+    "SAME",
+    "TWILIGHT",
+];
+
+/// Splits the slug of a scene heading into a [`SceneHeading`].
+///
+/// The slug is split on dashes surrounded by spaces. With three parts or more, the first part is
+/// the location, the last the time of day and everything in between the sub-location. With two
+/// parts, the last part is only the time of day if it has a word like `DAY` or `LATER` in it.
+pub(crate) fn parse_scene_heading(slug: &RichString) -> SceneHeading {
+    let text = slug.plain_text();
+    let mut rest = text.trim();
+
+    let mut setting = None;
+    for (prefix, s) in SETTINGS {
+        if Parser::starts_with_ascii_ci(rest, prefix)
+            && !rest[prefix.len()..].starts_with(char::is_alphanumeric)
+        {
+            setting = Some(s);
+            rest = rest[prefix.len()..].trim_start_matches(['.', ' ']);
+            break;
+        }
+    }
+
+    let mut parts = rest
+        .split([' ', '\u{a0}'])
+        .collect::<Vec<&str>>()
+        .split(|word| matches!(*word, "-" | "–" | "—"))
+        .map(|words| words.join(" ").trim().to_string())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<String>>();
+
+    let is_time = |part: &str| {
+        part.split(|c: char| !c.is_alphanumeric())
+            .any(|word| TIMES_OF_DAY.iter().any(|t| t.eq_ignore_ascii_case(word)))
+    };
+    let time = match parts.len() {
+        0 | 1 => None,
+        2 if !is_time(&parts[1]) => None,
+        _ => parts.pop(),
+    };
+    let sub_location = (parts.len() > 1).then(|| parts[1..].join(" - "));
+    let location = parts.into_iter().next().unwrap_or_default();
+
+    SceneHeading {
+        setting,
+        location,
+        sub_location,
+        time,
+    }
+}
+
+/// The last text of an [`Element`], if it has any text that is not a note of its own.
+fn last_rich_string(element: &mut Element) -> Option<&mut RichString> {
+    match element {
+        Element::Section { title: rs, .. }
+        | Element::Heading { slug: rs, .. }
+        | Element::Action(rs)
+        | Element::Lyrics(rs)
+        | Element::Transition(rs)
+        | Element::CenteredText(rs)
+        | Element::Synopsis(rs) => Some(rs),
+        Element::Dialogue(dialogue) | Element::DualDialogue(_, Spanned { node: dialogue, .. }) => {
+            match dialogue.elements.last_mut().map(|e| &mut e.node) {
+                Some(DialogueElement::Parenthetical(rs) | DialogueElement::Line(rs)) => Some(rs),
+                None => Some(&mut dialogue.character),
+            }
+        }
+        Element::Note(_) | Element::Boneyard(_) | Element::PageBreak => None,
+    }
+}
+
+/// Maps offsets in the preprocessed source back to [`Position`]s in the original source.
+struct SourceMap<'a> {
+    original: &'a str,
+    /// Where `original` starts, when it is a part of a larger source.
+    start: Position,
+    /// The offset in `original` of every byte of the preprocessed source, followed by the
+    /// length of `original`.
+    offsets: Vec<usize>,
+    /// The offset in `original` of the first byte of every line.
+    line_starts: Vec<usize>,
+}
+
+impl<'a> SourceMap<'a> {
+    fn new(original: &'a str, offsets: Vec<usize>, start: Position) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(original.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            original,
+            start,
+            offsets,
+            line_starts,
+        }
+    }
+
+    /// The [`Position`] of an offset in the original source.
+    fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = self.original[line_start..offset].chars().count() + 1;
+
+        Position {
+            offset: self.start.offset + offset,
+            line: self.start.line + line - 1,
+            column,
+        }
+    }
+
+    /// The [`Span`] in the original source of the range `start..end` in the preprocessed source.
+    fn span(&self, start: usize, end: usize) -> Span {
+        let original_start = self.offsets[start];
+        let original_end = if end > start {
+            // The end is exclusive, so step past the last byte that is included
+            self.offsets[end - 1] + 1
+        } else {
+            original_start
+        };
+
+        Span::new(self.position(original_start), self.position(original_end))
+    }
+
+    /// The [`Span`] of a range of the original source.
+    fn original_span(&self, range: Range<usize>) -> Span {
+        Span::new(self.position(range.start), self.position(range.end))
+    }
+}
+
+/// The source after [`preprocess_source`], together with what is needed to trace it back to
+/// the original source.
+struct Preprocessed {
+    source: String,
+    /// The offset in the original source that every byte of `source` originates from,
+    /// followed by the length of the original source.
+    offsets: Vec<usize>,
+    /// The notes and boneyards removed from the source, in order.
+    trivia: Vec<Trivia>,
+    /// Problems found while preprocessing, with their ranges in the original source.
+    warnings: Vec<(&'static str, Range<usize>)>,
+}
+
+/// A note (`[[note]]`) or boneyard (`/* boneyard */`) removed from the source during
+/// preprocessing, to be put back where it was written after parsing.
+struct Trivia {
+    kind: TriviaKind,
+    /// The offset in the preprocessed source where the trivia was removed.
+    anchor: usize,
+    /// The text within the trivia. Notes have all whitespace collapsed, while boneyards are
+    /// kept exactly as written.
+    text: String,
+    /// The range of the whole trivia in the original source.
+    range: Range<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum TriviaKind {
+    Note,
+    Boneyard,
+}
+
+impl Trivia {
+    /// Turns the [`Trivia`] into an [`Element`] of its own.
+    fn into_element(self) -> Element {
+        match self.kind {
+            TriviaKind::Note => Element::Note(RichString {
+                elements: vec![rich_string::Element::new(self.text)],
+            }),
+            TriviaKind::Boneyard => Element::Boneyard(self.text),
+        }
+    }
+
+    /// Turns the [`Trivia`] into a part of a [`RichString`].
+    fn into_rich_element(self) -> rich_string::Element {
+        match self.kind {
+            TriviaKind::Note => rich_string::Element::note(self.text),
+            TriviaKind::Boneyard => rich_string::Element::boneyard(self.text),
+        }
+    }
+}
+
+/// The warning for a boneyard that is never closed.
+pub(crate) const UNTERMINATED_BONEYARD: &str =
+    "unterminated boneyard, the rest of the script is cut";
+
+/// Removes boneyards, notes and normalizes tabs to four spaces. Escaped with `\`, a `/` or `[`
+/// does not start a boneyard or note. If `within_boneyard`, the source starts within a boneyard
+/// opened before it.
+///
+/// Also keeps the offset in `src` that every byte of the preprocessed source originates from,
+/// so that positions can be mapped back to the original source, as well as the removed
+/// [`Trivia`].
+fn preprocess_source(src: &str, within_boneyard: bool) -> Preprocessed {
     let bytes = src.as_bytes();
     let mut out = Vec::with_capacity(bytes.len());
+    let mut offsets = Vec::with_capacity(bytes.len());
     let mut i = 0;
-    let mut in_boneyard = false;
+    let mut in_boneyard = within_boneyard;
+    // Where the current boneyard starts, and where its text starts after the opening `/*`
+    let (mut boneyard_start, mut text_start) = (0, 0);
+    let mut boneyards = Vec::new();
+    let mut warnings = Vec::new();
 
     // Filter out boneyard and replace tabs
     while i < bytes.len() {
@@ -463,6 +950,7 @@ fn preprocess_source(src: &str) -> String {
             // Inside boneyard: preserve only newlines so that a boneyard can cover all whitespace
             if b == b'\n' {
                 out.push(b'\n');
+                offsets.push(i);
                 i += 1;
                 continue;
             }
@@ -470,6 +958,7 @@ fn preprocess_source(src: &str) -> String {
             // Check if at the end of boneyard
             if i + 1 < bytes.len() && b == b'*' && bytes[i + 1] == b'/' {
                 in_boneyard = false;
+                boneyards.push((boneyard_start, text_start, i + 2));
                 i += 2;
                 continue;
             }
@@ -479,6 +968,15 @@ fn preprocess_source(src: &str) -> String {
             // Check if at the start of a boneyard
             if i + 1 < bytes.len() && b == b'/' && bytes[i + 1] == b'*' {
                 in_boneyard = true;
+                (boneyard_start, text_start) = (i, i + 2);
+                i += 2;
+                continue;
+            }
+
+            // An escaped `/` never starts a boneyard
+            if b == b'\\' && matches!(bytes.get(i + 1), Some(b'\\' | b'/')) {
+                out.extend_from_slice(&bytes[i..i + 2]);
+                offsets.extend([i, i + 1]);
                 i += 2;
                 continue;
             }
@@ -486,25 +984,57 @@ fn preprocess_source(src: &str) -> String {
             // A Fountain-specified tab: 4 spaces
             if b == b'\t' {
                 out.extend_from_slice(b"    ");
+                offsets.extend([i; 4]);
                 i += 1;
                 continue;
             }
 
             // Normal character
             out.push(b);
+            offsets.push(i);
             i += 1;
         }
     }
+    if in_boneyard {
+        boneyards.push((boneyard_start, text_start, bytes.len()));
+        // A boneyard opened before the source is warned about where it was opened
+        if text_start > boneyard_start {
+            warnings.push((UNTERMINATED_BONEYARD, boneyard_start..text_start));
+        }
+    }
+
+    // Anchor the boneyards where they started, in the source without boneyards
+    let mut boneyards = boneyards
+        .into_iter()
+        .map(|(start, text_start, end)| Trivia {
+            kind: TriviaKind::Boneyard,
+            anchor: offsets.partition_point(|&offset| offset < start),
+            text: src[text_start..end]
+                .strip_suffix("*/")
+                .unwrap_or(&src[text_start..end])
+                .to_string(),
+            range: start..end,
+        })
+        .peekable();
 
     // Filter out notes
     let mut final_out = Vec::with_capacity(out.len());
+    let mut final_offsets = Vec::with_capacity(out.len() + 1);
     i = 0;
     let mut in_note = false;
     let mut note_buffer = Vec::new();
+    let mut note_offsets = Vec::new();
+    let mut trivia = Vec::new();
 
     while i < out.len() {
         let b = out[i];
 
+        // Move the anchors of the boneyards reached into the source without notes
+        while let Some(mut boneyard) = boneyards.next_if(|t| t.anchor <= i) {
+            boneyard.anchor = final_out.len();
+            trivia.push(boneyard);
+        }
+
         if in_note {
             // Inside note: preserve only newlines so that a note can cover all whitespace
             if b == b'\n' {
@@ -518,18 +1048,24 @@ fn preprocess_source(src: &str) -> String {
                     note_buffer.push(b' ');
                     note_buffer.push(b' ');
                     note_buffer.push(b'\n');
+                    note_offsets.extend_from_slice(&offsets[i..i + 4]);
                     i += 4;
                     continue;
                 }
                 // Exit note without closing it
                 if i + 1 < out.len() && out[i + 1] == b'\n' {
+                    in_note = false;
+                    warnings.push(("unclosed note, kept as text", unclosed_note(&note_offsets)));
                     final_out.append(&mut note_buffer);
+                    final_offsets.append(&mut note_offsets);
                     final_out.push(b'\n');
                     final_out.push(b'\n');
+                    final_offsets.extend_from_slice(&offsets[i..i + 2]);
                     i += 2;
                     continue;
                 }
                 note_buffer.push(b'\n');
+                note_offsets.push(offsets[i]);
                 i += 1;
                 continue;
             }
@@ -537,30 +1073,72 @@ fn preprocess_source(src: &str) -> String {
             // Check if at the end of note
             if i + 1 < out.len() && b == b']' && out[i + 1] == b']' {
                 in_note = false;
+                let text = String::from_utf8_lossy(&note_buffer[2..]);
+                trivia.push(Trivia {
+                    kind: TriviaKind::Note,
+                    anchor: final_out.len(),
+                    text: text.split_whitespace().collect::<Vec<&str>>().join(" "),
+                    range: note_offsets[0]..offsets[i + 1] + 1,
+                });
                 note_buffer = Vec::new();
+                note_offsets = Vec::new();
                 i += 2;
                 continue;
             }
 
             note_buffer.push(b);
+            note_offsets.push(offsets[i]);
             i += 1;
         } else {
+            // An escaped `[` never starts a note
+            if b == b'\\' && matches!(out.get(i + 1), Some(b'\\' | b'[')) {
+                final_out.extend_from_slice(&out[i..i + 2]);
+                final_offsets.extend_from_slice(&offsets[i..i + 2]);
+                i += 2;
+                continue;
+            }
+
             // Check if at the start of a note
             if i + 1 < out.len() && b == b'[' && out[i + 1] == b'[' {
                 in_note = true;
                 note_buffer = vec![b'[', b'['];
+                note_offsets = offsets[i..i + 2].to_vec();
                 i += 2;
                 continue;
             }
 
             // Normal character
             final_out.push(b);
+            final_offsets.push(offsets[i]);
             i += 1;
         }
     }
+    if in_note {
+        warnings.push(("unclosed note, kept as text", unclosed_note(&note_offsets)));
+    }
     final_out.append(&mut note_buffer);
+    final_offsets.append(&mut note_offsets);
+    final_offsets.push(src.len());
+
+    for mut boneyard in boneyards {
+        boneyard.anchor = final_out.len();
+        trivia.push(boneyard);
+    }
+    trivia.sort_by_key(|t| (t.anchor, t.range.start));
+
+    // Only whole runs of bytes between ASCII delimiters (`/*`, `*/`, `[[`, `]]` and newlines)
+    // are removed, and only ASCII tabs are replaced, so no multi-byte character is ever split
+    Preprocessed {
+        source: String::from_utf8(final_out).expect("Only ASCII is removed or replaced"),
+        offsets: final_offsets,
+        trivia,
+        warnings,
+    }
+}
 
-    String::from_utf8(final_out).expect("Valid UTF-8 after preprocessing")
+/// The range of the opening `[[` of an unclosed note, given the offsets of the note.
+fn unclosed_note(note_offsets: &[usize]) -> Range<usize> {
+    note_offsets[0]..note_offsets[1] + 1
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -580,6 +1158,13 @@ mod tests {
         assert_eq!(parsed, correct)
     }
 
+    fn noted(before: &str, note: &str, after: &str) -> RichString {
+        let mut rs = RichString::from(before);
+        rs.push_trivia(rich_string::Element::note(note.to_string()));
+        rs.append(RichString::from(after));
+        rs
+    }
+
     #[test]
     fn parses_heading_without_number() {
         let input = "InT. OUTSIDE - DAY";
@@ -672,10 +1257,10 @@ I am angry.
                 character: "CHAR".into(),
                 extension: None,
                 elements: vec![
-                    DialogueElement::Parenthetical("(sad)".into()),
-                    DialogueElement::Line("Nooo!".into()),
-                    DialogueElement::Parenthetical("(angry)".into()),
-                    DialogueElement::Line("I am angry.".into()),
+                    DialogueElement::Parenthetical("(sad)".into()).into(),
+                    DialogueElement::Line("Nooo!".into()).into(),
+                    DialogueElement::Parenthetical("(angry)".into()).into(),
+                    DialogueElement::Line("I am angry.".into()).into(),
                 ],
             })],
         );
@@ -696,8 +1281,8 @@ Nooo!
                 character: "CHAR".into(),
                 extension: Some("V.O".into()),
                 elements: vec![
-                    DialogueElement::Parenthetical("(sad)".into()),
-                    DialogueElement::Line("Nooo!".into()),
+                    DialogueElement::Parenthetical("(sad)".into()).into(),
+                    DialogueElement::Line("Nooo!".into()).into(),
                 ],
             })],
         );
@@ -720,10 +1305,10 @@ I am angry.
                 character: "char".into(),
                 extension: None,
                 elements: vec![
-                    DialogueElement::Parenthetical("(sad)".into()),
-                    DialogueElement::Line("Nooo!".into()),
-                    DialogueElement::Parenthetical("(angry)".into()),
-                    DialogueElement::Line("I am angry.".into()),
+                    DialogueElement::Parenthetical("(sad)".into()).into(),
+                    DialogueElement::Line("Nooo!".into()).into(),
+                    DialogueElement::Parenthetical("(angry)".into()).into(),
+                    DialogueElement::Line("I am angry.".into()).into(),
                 ],
             })],
         );
@@ -744,8 +1329,8 @@ Nooo!
                 character: "char".into(),
                 extension: Some("V.O".into()),
                 elements: vec![
-                    DialogueElement::Parenthetical("(sad)".into()),
-                    DialogueElement::Line("Nooo!".into()),
+                    DialogueElement::Parenthetical("(sad)".into()).into(),
+                    DialogueElement::Line("Nooo!".into()).into(),
                 ],
             })],
         );
@@ -770,15 +1355,17 @@ YES!
                     character: "CHaR".into(),
                     extension: None,
                     elements: vec![
-                        DialogueElement::Parenthetical("(sad)".into()),
-                        DialogueElement::Line("Nooo!".into()),
+                        DialogueElement::Parenthetical("(sad)".into()).into(),
+                        DialogueElement::Line("Nooo!".into()).into(),
                     ],
-                },
+                }
+                .into(),
                 Dialogue {
                     character: "CHOR".into(),
                     extension: Some("V.O".into()),
-                    elements: vec![DialogueElement::Line("YES!".into())],
-                },
+                    elements: vec![DialogueElement::Line("YES!".into()).into()],
+                }
+                .into(),
             )],
         );
 
@@ -847,7 +1434,7 @@ YES!
     }
 
     #[test]
-    fn does_not_parse_section() {
+    fn parses_section() {
         let input = r"
 # Act 1
 
@@ -860,10 +1447,18 @@ House is empty.";
         let correct = Screenplay::new(
             None,
             vec![
+                Element::Section {
+                    depth: 1,
+                    title: "Act 1".into(),
+                },
                 Element::Heading {
                     slug: "INT. HOUSE".into(),
                     number: None,
                 },
+                Element::Section {
+                    depth: 2,
+                    title: "Montage".into(),
+                },
                 Element::Action("House is empty.".into()),
             ],
         );
@@ -928,7 +1523,63 @@ House is empty.";
     }
 
     #[test]
-    fn filters_out_note_multiline() {
+    fn keeps_boneyard_with_option() {
+        let input = r"
+INT. HOUSE
+
+/* This is a boneyard
+, you understand?*/
+
+House is empty.";
+
+        let parsed = parse_with_options(
+            input,
+            ParseOptions {
+                boneyards: true,
+                ..Default::default()
+            },
+        );
+        let correct = Screenplay::new(
+            None,
+            vec![
+                Element::Heading {
+                    slug: "INT. HOUSE".into(),
+                    number: None,
+                },
+                Element::Boneyard(" This is a boneyard\n, you understand?".to_string()),
+                Element::Action("House is empty.".into()),
+            ],
+        );
+
+        assert_eq!(parsed, correct);
+        assert_eq!(parsed.elements[1].span.range(), 13..54);
+    }
+
+    #[test]
+    fn keeps_boneyard_inlined_with_option() {
+        let input = "The house is /*extremely full*/**empty**.";
+
+        let parsed = parse_with_options(
+            input,
+            ParseOptions {
+                boneyards: true,
+                ..Default::default()
+            },
+        );
+        let Element::Action(action) = &parsed.elements[0].node else {
+            panic!("Expected an action");
+        };
+
+        assert_eq!(action.elements.len(), 4);
+        assert_eq!(action.elements[0].text, "The house is ".to_string());
+        assert!(action.elements[1].is_boneyard());
+        assert_eq!(action.elements[1].text, "extremely full".to_string());
+        assert!(action.elements[2].is_bold());
+        assert_eq!(action.elements[2].text, "empty".to_string());
+    }
+
+    #[test]
+    fn keeps_note_multiline() {
         let input = r"
 INT. HOUSE
 
@@ -945,6 +1596,11 @@ House is empty.";
                     slug: "INT. HOUSE".into(),
                     number: None,
                 },
+                Element::Note(RichString {
+                    elements: vec![rich_string::Element::new(
+                        "This is a note and should not be parsed , you understand?".to_string(),
+                    )],
+                }),
                 Element::Action("House is empty.".into()),
             ],
         );
@@ -953,16 +1609,23 @@ House is empty.";
     }
 
     #[test]
-    fn filters_out_note_inlined() {
+    fn keeps_note_inlined() {
         let input = "The house is [[should it be full?]]empty.";
 
-        let correct = Screenplay::new(None, vec![Element::Action("The house is empty.".into())]);
+        let correct = Screenplay::new(
+            None,
+            vec![Element::Action(noted(
+                "The house is ",
+                "should it be full?",
+                "empty.",
+            ))],
+        );
 
         parser_tester(input, correct)
     }
 
     #[test]
-    fn filters_out_note_inlined_multiline() {
+    fn keeps_note_inlined_multiline() {
         let input = r"
 INT. HOUSE
 
@@ -977,7 +1640,11 @@ The house [[ This is a note
                     slug: "INT. HOUSE".into(),
                     number: None,
                 },
-                Element::Action("The house is empty.".into()),
+                Element::Action(noted(
+                    "The house ",
+                    "This is a note and should not be parsed , you understand?",
+                    "is empty.",
+                )),
             ],
         );
 
@@ -985,7 +1652,7 @@ The house [[ This is a note
     }
 
     #[test]
-    fn filters_out_note_multiline_empty_newline() {
+    fn keeps_note_multiline_empty_newline() {
         let input = r"
 INT. HOUSE
 
@@ -1001,13 +1668,42 @@ The house [[This is a note
                     slug: "INT. HOUSE".into(),
                     number: None,
                 },
-                Element::Action("The house is empty.".into()),
+                Element::Action(noted(
+                    "The house ",
+                    "This is a note and should not be parsed , you understand?",
+                    "is empty.",
+                )),
             ],
         );
 
         parser_tester(input, correct)
     }
 
+    #[test]
+    fn keeps_note_after_character() {
+        let input = "BOB (V.O.) [[too quiet?]]\nHello.";
+
+        let correct = Screenplay::new(
+            None,
+            vec![Element::Dialogue(Dialogue {
+                character: noted("BOB", "too quiet?", ""),
+                extension: Some("V.O.".into()),
+                elements: vec![DialogueElement::Line("Hello.".into()).into()],
+            })],
+        );
+
+        parser_tester(input, correct)
+    }
+
+    #[test]
+    fn spans_block_note() {
+        let input = "Action.\n\n[[A note]]\n\nMore action.";
+        let parsed = parse(input);
+
+        assert!(matches!(parsed.elements[1].node, Element::Note(_)));
+        assert_eq!(&input[parsed.elements[1].span.range()], "[[A note]]");
+    }
+
     #[test]
     fn not_filters_out_unended_note_multiline() {
         let input = r"
@@ -1040,4 +1736,270 @@ no";
 
         parser_tester(input, correct)
     }
+
+    #[test]
+    fn spans_point_into_original_source() {
+        let input = "/* cut\nscene */INT. HOUSE\n\n\tThe [[note]]house is empty.";
+        let parsed = parse(input);
+
+        let heading = parsed.elements[0].span;
+        assert_eq!(&input[heading.range()], "INT. HOUSE");
+        assert_eq!((heading.start.line, heading.start.column), (2, 9));
+
+        let action = parsed.elements[1].span;
+        assert_eq!(&input[action.range()], "The [[note]]house is empty.");
+        assert_eq!((action.start.line, action.start.column), (4, 2));
+        assert_eq!((action.end.line, action.end.column), (4, 29));
+    }
+
+    #[test]
+    fn spans_dialogue_elements() {
+        let input = "BOB\n(quietly)\nHello.\n\nALICE ^\nHi.";
+        let parsed = parse(input);
+
+        let Element::DualDialogue(first, second) = &parsed.elements[0].node else {
+            panic!("Expected dual dialogue");
+        };
+        assert_eq!(&input[parsed.elements[0].span.range()], input);
+        assert_eq!(&input[first.span.range()], "BOB\n(quietly)\nHello.");
+        assert_eq!(&input[second.span.range()], "ALICE ^\nHi.");
+        assert_eq!(&input[first.elements[0].span.range()], "(quietly)");
+        assert_eq!(second.elements[0].span.start.line, 6);
+    }
+
+    #[test]
+    fn spans_titlepage() {
+        let input = "Title: Test\nAuthor:\n    Someone\n\nINT. HOUSE";
+        let parsed = parse(input);
+
+        let titlepage = parsed.titlepage.expect("Has a title page");
+        assert_eq!(
+            &input[titlepage.span.range()],
+            "Title: Test\nAuthor:\n    Someone"
+        );
+        assert_eq!(parsed.elements[0].span.start.line, 5);
+    }
+
+    fn heading(slug: &str) -> SceneHeading {
+        parse_scene_heading(&slug.into())
+    }
+
+    #[test]
+    fn splits_scene_heading() {
+        assert_eq!(
+            heading("INT. HOUSE - KITCHEN - DAY"),
+            SceneHeading {
+                setting: Some(Setting::Interior),
+                location: "HOUSE".to_string(),
+                sub_location: Some("KITCHEN".to_string()),
+                time: Some("DAY".to_string()),
+            }
+        );
+        assert_eq!(
+            heading("ext. mountain-top - moments later"),
+            SceneHeading {
+                setting: Some(Setting::Exterior),
+                location: "mountain-top".to_string(),
+                sub_location: None,
+                time: Some("moments later".to_string()),
+            }
+        );
+        assert_eq!(
+            heading("EXT./INT. CAR – CONTINUOUS"),
+            SceneHeading {
+                setting: Some(Setting::ExteriorInterior),
+                location: "CAR".to_string(),
+                sub_location: None,
+                time: Some("CONTINUOUS".to_string()),
+            }
+        );
+        assert_eq!(
+            heading("I/E SHIP - DECK").setting,
+            Some(Setting::InteriorExterior)
+        );
+        assert_eq!(
+            heading("I/E SHIP - DECK").sub_location,
+            Some("DECK".to_string())
+        );
+        assert_eq!(
+            heading("EST. CITY - NIGHT").setting,
+            Some(Setting::Establishing)
+        );
+    }
+
+    #[test]
+    fn splits_scene_heading_without_setting() {
+        let parsed = parse(".SNIPER SCOPE POV\n\nA target.");
+        let Some(forced) = parsed.elements[0].scene_heading() else {
+            panic!("Expected a heading");
+        };
+
+        assert_eq!(
+            forced,
+            SceneHeading {
+                setting: None,
+                location: "SNIPER SCOPE POV".to_string(),
+                sub_location: None,
+                time: None,
+            }
+        );
+        assert_eq!(heading("INTERIOR DESIGN - DAY").setting, None);
+    }
+
+    #[test]
+    fn keeps_extra_titlepage_keys() {
+        let input = "Title: Pilot\nSeries: The Show\nNotes:\n    First draft\n    Do not share\n\
+                     Copyright: (c) 2025\n\nINT. HOUSE";
+        let parsed = parse(input);
+
+        let titlepage = parsed.titlepage.expect("Has a title page");
+        assert_eq!(titlepage.title, vec!["Pilot".into()]);
+        assert_eq!(
+            titlepage.extra,
+            vec![
+                ("Series".to_string(), vec!["The Show".into()]),
+                (
+                    "Notes".to_string(),
+                    vec!["First draft".into(), "Do not share".into()]
+                ),
+                ("Copyright".to_string(), vec!["(c) 2025".into()]),
+            ]
+        );
+        assert_eq!(titlepage.get("notes").map(<[_]>::len), Some(2));
+    }
+
+    #[test]
+    fn trims_titlepage_values() {
+        let input = "Title:   Pilot  \nNotes:\n    First draft   \n\nINT. HOUSE";
+        let titlepage = parse(input).titlepage.expect("Has a title page");
+
+        assert_eq!(titlepage.get("title"), Some(&["Pilot".into()][..]));
+        assert_eq!(titlepage.get("notes"), Some(&["First draft".into()][..]));
+        assert_eq!(titlepage.get("credit"), None);
+    }
+
+    #[test]
+    fn warns_unterminated_boneyard() {
+        let input = "INT. HOUSE\n\n/* This is cut\n\nHouse is empty.";
+        let (parsed, diagnostics) = parse_with_diagnostics(input, ParseOptions::default());
+
+        assert_eq!(parsed.elements.len(), 1);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(&input[diagnostics[0].span.range()], "/*");
+        assert_eq!(diagnostics[0].span.start.line, 3);
+    }
+
+    #[test]
+    fn warns_unclosed_note() {
+        let input = "House is [[not\n\nempty.\n\nBOB\nHi [[there";
+        let (_, diagnostics) = parse_with_diagnostics(input, ParseOptions::default());
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].span.range(), 9..11);
+        assert_eq!(diagnostics[1].span.start.line, 6);
+        assert_eq!(&input[diagnostics[1].span.range()], "[[");
+    }
+
+    #[test]
+    fn warns_dual_dialogue_caret_without_dialogue() {
+        let input = "The house is empty.\n\nBOB ^\nHello?";
+        let (parsed, diagnostics) = parse_with_diagnostics(input, ParseOptions::default());
+
+        assert!(matches!(parsed.elements[1].node, Element::Dialogue(_)));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(&input[diagnostics[0].span.range()], "^");
+        assert_eq!(diagnostics[0].span.start.column, 5);
+    }
+
+    #[test]
+    fn no_diagnostics_for_wellformed_source() {
+        let input = "INT. HOUSE\n\n/* cut */\n\nBOB\nHi [[note]].\n\nALICE ^\nHello.";
+        let (_, diagnostics) = parse_with_diagnostics(input, ParseOptions::default());
+
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn preprocesses_multibyte_text_around_trivia() {
+        let preprocessed = preprocess_source("é/*ü\n*/ö\t田[[ß\nä]]。/*ñ", false);
+
+        assert_eq!(preprocessed.source, "é\nö    田。");
+        assert_eq!(preprocessed.trivia.len(), 3);
+    }
+
+    #[test]
+    fn escaped_markup_does_not_start_trivia() {
+        let screenplay = parse(r"and\/**or** a\[[b]]");
+
+        let Element::Action(action) = &screenplay.elements[0].node else {
+            panic!("expected action");
+        };
+        assert_eq!(screenplay.elements.len(), 1);
+        assert_eq!(action.plain_text(), "and/or a[[b]]");
+        assert!(action.elements[1].is_bold());
+    }
+
+    #[test]
+    fn forces_caseless_cues() {
+        let screenplay = parse("@田中\nこんにちは。\n\n@שרה (V.O.)\nשלום.");
+
+        let Element::Dialogue(first) = &screenplay.elements[0].node else {
+            panic!("expected dialogue");
+        };
+        assert_eq!(first.character, "田中".into());
+
+        let Element::Dialogue(second) = &screenplay.elements[1].node else {
+            panic!("expected dialogue");
+        };
+        assert_eq!(second.character, "שרה".into());
+        assert_eq!(second.extension, Some("V.O.".into()));
+    }
+
+    #[test]
+    fn detects_caseless_cues() {
+        let src =
+            "田中\nこんにちは。\n\n部屋に入る。\n静かだ。\n\nשרה (V.O.)\nשלום.\n\nيدخل سامي.\nيجلس";
+        let options = ParseOptions {
+            caseless_cues: true,
+            ..Default::default()
+        };
+        let screenplay = parse_with_options(src, options);
+
+        let Element::Dialogue(first) = &screenplay.elements[0].node else {
+            panic!("expected dialogue");
+        };
+        assert_eq!(first.character, "田中".into());
+        assert!(matches!(screenplay.elements[1].node, Element::Action(_)));
+
+        let Element::Dialogue(second) = &screenplay.elements[2].node else {
+            panic!("expected dialogue");
+        };
+        assert_eq!(second.character, "שרה".into());
+        assert_eq!(second.extension, Some("V.O.".into()));
+        assert!(matches!(screenplay.elements[3].node, Element::Action(_)));
+    }
+
+    #[test]
+    fn keeps_caseless_action() {
+        let src = "部屋に入る\n静かだ。\n\nשרה\nיושבת ליד החלון\n\nيدخل سامي\nيجلس";
+        let screenplay = parse(src);
+
+        assert_eq!(screenplay.elements.len(), 3);
+        assert!(
+            screenplay
+                .elements
+                .iter()
+                .all(|e| matches!(e.node, Element::Action(_)))
+        );
+    }
+
+    #[test]
+    fn trims_dual_dialogue_caret() {
+        let screenplay = parse("BOB\nHi.\n\nALICE ^\nHey.");
+
+        let Element::DualDialogue(_, second) = &screenplay.elements[0].node else {
+            panic!("expected dual dialogue");
+        };
+        assert_eq!(second.character, "ALICE".into());
+    }
 }
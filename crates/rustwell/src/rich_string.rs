@@ -6,14 +6,20 @@
 //! ```
 //! use rustwell::rich_string::RichString;
 //!
-//! let rs: RichString = "_Hello _**world!**".into();
+//! let rs: RichString = "_Hello_ **world!**".into();
 //!
-//! assert_eq!(rs.elements[0].text, "Hello ".to_string());
+//! assert_eq!(rs.elements[0].text, "Hello".to_string());
 //! assert!(rs.elements[0].is_underline());
-//! assert_eq!(rs.elements[1].text, "world!".to_string());
-//! assert!(rs.elements[1].is_bold());
+//! assert_eq!(rs.elements[2].text, "world!".to_string());
+//! assert!(rs.elements[2].is_bold());
 //! ```
 
+use std::{
+    fmt,
+    iter::Peekable,
+    ops::{Bound, Range, RangeBounds},
+};
+
 use bitflags::bitflags;
 
 /// A string that can have different parts styled.
@@ -27,8 +33,11 @@ use bitflags::bitflags;
 /// - `_underline_` → <u>underline</u>
 ///
 /// as specified in the [Fountain specification](https://fountain.io/syntax/). Furthermore,
-/// these can be combined in any overlapping order. Use `\` for a styling character to be
-/// ignored for style parsing.
+/// these can be nested in any order. A style only applies when its markers pair up within the
+/// same line, an opening marker followed by text and a closing one following text. Markers
+/// that do not pair up, like in `5 * 3 = 15`, are kept as they are. Use `\` for a styling
+/// character to be ignored for style parsing. An escaped character always counts as text, so
+/// `**bold\ **` is bold up to and including the space.
 ///
 /// # Examples
 ///
@@ -43,6 +52,11 @@ use bitflags::bitflags;
 /// assert!(rs.elements[1].is_bold());
 /// ```
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct RichString {
     pub elements: Vec<Element>,
 }
@@ -58,56 +72,202 @@ impl RichString {
     /// Pushes a string onto the [RichString]. Will divide the string into multiple elements with
     /// different styles if input string can be parsed with styles.
     pub fn push_str(&mut self, str: impl AsRef<str>) {
-        let s = str.as_ref();
-        let bytes = s.as_bytes();
+        self.push_str_with_trivia(str.as_ref(), Vec::new());
+    }
+
+    /// Pushes a string onto the [RichString] with the given [Style], without parsing it for
+    /// styles. New lines are still pushed without any style.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustwell::rich_string::{RichString, Style};
+    ///
+    /// let mut rs = RichString::new();
+    /// rs.push_styled("5 * 3", Style::BOLD);
+    ///
+    /// assert_eq!(rs.elements[0].text, "5 * 3".to_string());
+    /// assert!(rs.elements[0].is_bold());
+    /// ```
+    pub fn push_styled(&mut self, str: impl AsRef<str>, style: Style) {
+        for (i, line) in str.as_ref().split('\n').enumerate() {
+            if i > 0 {
+                self.push_run('\n'.to_string(), Style::empty());
+            }
+            self.push_run(line.to_string(), style);
+        }
+    }
+
+    /// The text of the [RichString] without any styles, notes or boneyards.
+    pub fn plain_text(&self) -> String {
+        self.text_elements().map(|e| e.text.as_str()).collect()
+    }
+
+    /// The number of [char]s in the [RichString], not counting notes and boneyards.
+    pub fn len(&self) -> usize {
+        self.text_elements().map(|e| e.text.chars().count()).sum()
+    }
+
+    /// If the [RichString] has no text, not counting notes and boneyards.
+    pub fn is_empty(&self) -> bool {
+        self.text_elements().all(|e| e.text.is_empty())
+    }
+
+    /// The part of the [RichString] in the given range of [char]s, with all styles kept. Notes
+    /// and boneyards are kept if they are placed in the range, or at the end of both the
+    /// [RichString] and the range. Returns [None] if the start of the range is after its end,
+    /// or its end is after [RichString::len].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustwell::rich_string::RichString;
+    ///
+    /// let rs = RichString::from("Hello **world!**");
+    /// let world = rs.slice(6..11).unwrap();
+    ///
+    /// assert_eq!(world.plain_text(), "world".to_string());
+    /// assert!(world.elements[0].is_bold());
+    /// assert_eq!(rs.slice(6..20), None);
+    /// ```
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Option<RichString> {
+        let len = self.len();
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1)?,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.checked_add(1)?,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => len,
+        };
+        (start <= end && end <= len).then(|| self.slice_chars(start, end, end == len))
+    }
+
+    /// Splits the [RichString] in two at the given [char], with all styles kept. Notes and
+    /// boneyards placed at `mid` go with the second part. Returns [None] if `mid` is after
+    /// [RichString::len].
+    pub fn split_at(&self, mid: usize) -> Option<(RichString, RichString)> {
+        let len = self.len();
+        (mid <= len).then(|| {
+            (
+                self.slice_chars(0, mid, false),
+                self.slice_chars(mid, len, true),
+            )
+        })
+    }
+
+    /// The [char]s from `start` to `end`, with the notes and boneyards placed from `start` up
+    /// to `end`, and also the ones at `end` if `trailing` is set. The range has to be within
+    /// the [RichString].
+    fn slice_chars(&self, start: usize, end: usize, trailing: bool) -> RichString {
+        let mut out = RichString::new();
+        let mut at = 0;
+        for element in &self.elements {
+            if element.is_note() || element.is_boneyard() {
+                if start <= at && (at < end || (trailing && at == end)) {
+                    out.push_trivia(element.clone());
+                }
+                continue;
+            }
+
+            let count = element.text.chars().count();
+            let from = start.clamp(at, at + count) - at;
+            let to = end.clamp(at, at + count) - at;
+            let text = element.text.chars().skip(from).take(to - from).collect();
+            out.push_run(text, element.style);
+            at += count;
+        }
+        out
+    }
+
+    /// The elements that are part of the text, so neither notes nor boneyards.
+    fn text_elements(&self) -> impl Iterator<Item = &Element> {
+        self.elements
+            .iter()
+            .filter(|e| !e.is_note() && !e.is_boneyard())
+    }
+
+    /// Pushes a string onto the [RichString] like [RichString::push_str], while inserting notes
+    /// and boneyards at the given byte offsets of the string. These do not break up any styling
+    /// around them.
+    pub(crate) fn push_str_with_trivia(&mut self, s: &str, trivia: Vec<(usize, Element)>) {
+        let mut trivia = trivia.into_iter().peekable();
+
+        // Styles never continue onto the next line, so every line is matched on its own
+        let mut start = 0;
+        for line in s.split_inclusive('\n') {
+            let text = line.strip_suffix('\n');
+            let end = start + text.map_or(line.len(), str::len);
+            self.push_line(s, start..end, &mut trivia);
+            if text.is_some() {
+                self.push_run('\n'.to_string(), Style::empty());
+            }
+            start += line.len();
+        }
+        for (_, element) in trivia {
+            self.push_trivia(element);
+        }
+    }
 
+    /// Pushes the line at `range` of `s`, with the trivia anchored at or before its end.
+    ///
+    /// Runs of `*` and `_` are first collected as [Delimiter]s and then matched with each other,
+    /// much like the delimiter stack of [CommonMark](https://spec.commonmark.org/0.31.2/#emphasis-and-strong-emphasis).
+    /// Only delimiters that are matched become styles, all others are kept as literal text.
+    fn push_line(
+        &mut self,
+        s: &str,
+        range: Range<usize>,
+        trivia: &mut Peekable<impl Iterator<Item = (usize, Element)>>,
+    ) {
+        let bytes = s.as_bytes();
+        let mut tokens = Vec::new();
+        let mut delimiters = Vec::new();
         let mut buf = String::new();
-        let mut attrs = Attributes::empty();
+        // Where the last escaped char ended, as an escaped char always counts as text
+        let mut escaped_end = None;
+
+        let mut i = range.start;
+        while i < range.end {
+            while let Some((_, element)) = trivia.next_if(|(at, _)| *at <= i) {
+                if !buf.is_empty() {
+                    tokens.push(Token::Text(std::mem::take(&mut buf)));
+                }
+                tokens.push(Token::Trivia(element));
+            }
 
-        let mut i = 0;
-        while i < bytes.len() {
             match bytes[i] {
-                b'*' => {
+                marker @ (b'*' | b'_') => {
                     if !buf.is_empty() {
-                        self.push_run(std::mem::take(&mut buf), attrs);
+                        tokens.push(Token::Text(std::mem::take(&mut buf)));
                     }
 
-                    let mut count = 0;
-                    while i < bytes.len() && bytes[i] == b'*' && count < 3 {
-                        count += 1;
+                    let run_start = i;
+                    while i < range.end && bytes[i] == marker {
                         i += 1;
                     }
-                    match count {
-                        1 => attrs ^= Attributes::ITALIC,
-                        2 => attrs ^= Attributes::BOLD,
-                        3 => attrs ^= Attributes::ITALIC | Attributes::BOLD,
-                        _ => unreachable!("Count can't be increased further than 3"),
-                    }
+                    let before = if escaped_end == Some(run_start) {
+                        Some('\\')
+                    } else {
+                        s[range.start..run_start].chars().next_back()
+                    };
+                    let after = s[i..range.end].chars().next();
+                    delimiters.push(Delimiter::new(marker, i - run_start, before, after));
+                    tokens.push(Token::Delimiter(delimiters.len() - 1));
                 }
-                b'_' => {
-                    if !buf.is_empty() {
-                        self.push_run(std::mem::take(&mut buf), attrs);
-                    }
-                    attrs ^= Attributes::UNDERLINE;
+                b'\\' => {
+                    // Discard the \ and push the next char, or keep it if nothing follows
                     i += 1;
-                }
-                b'\n' => {
-                    if !buf.is_empty() {
-                        self.push_run(std::mem::take(&mut buf), attrs);
+                    match s[i..range.end].chars().next() {
+                        Some(ch) => {
+                            buf.push(ch);
+                            i += ch.len_utf8();
+                            escaped_end = Some(i);
+                        }
+                        None => buf.push('\\'),
                     }
-                    attrs = Attributes::empty();
-                    self.push_run('\n'.to_string(), Attributes::empty());
-                    i += 1;
-                }
-                b'\\' => {
-                    let back_slash = s[i..].chars().next().expect("Should be a valid charpoint");
-                    i += back_slash.len_utf8();
-                    // Discard the \
-
-                    // Push the next char
-                    let ch = s[i..].chars().next().expect("Should be a valid charpoint");
-                    buf.push(ch);
-                    i += ch.len_utf8();
                 }
                 _ => {
                     let ch = s[i..].chars().next().expect("Should be a valid charpoint");
@@ -117,23 +277,68 @@ impl RichString {
             }
         }
         if !buf.is_empty() {
-            self.push_run(std::mem::take(&mut buf), attrs);
+            tokens.push(Token::Text(buf));
         }
+        while let Some((_, element)) = trivia.next_if(|(at, _)| *at <= range.end) {
+            tokens.push(Token::Trivia(element));
+        }
+
+        match_delimiters(&mut delimiters);
+
+        // The styles currently open, one entry for every matched opener
+        let mut open: Vec<Style> = Vec::new();
+        let current = |open: &[Style]| open.iter().fold(Style::empty(), |acc, &style| acc | style);
+        for token in tokens {
+            match token {
+                Token::Text(text) => self.push_run(text, current(&open)),
+                Token::Trivia(element) => self.push_trivia(element),
+                Token::Delimiter(index) => {
+                    let delimiter = &delimiters[index];
+                    for style in &delimiter.closes {
+                        if let Some(pos) = open.iter().rposition(|s| s == style) {
+                            open.remove(pos);
+                        }
+                    }
+                    let literal = char::from(delimiter.marker)
+                        .to_string()
+                        .repeat(delimiter.remaining);
+                    self.push_run(literal, current(&open));
+                    open.extend(&delimiter.opens);
+                }
+            }
+        }
+    }
+
+    /// Appends all elements of another [RichString] onto this one.
+    pub(crate) fn append(&mut self, other: RichString) {
+        for element in other.elements {
+            if element.is_note() || element.is_boneyard() {
+                self.push_trivia(element);
+            } else {
+                self.push_run(element.text, element.style);
+            }
+        }
+    }
+
+    /// Pushes a note or boneyard as its own element, never merged with the elements around it.
+    pub(crate) fn push_trivia(&mut self, element: Element) {
+        self.elements.push(element);
     }
 
-    fn push_run(&mut self, text: String, attributes: Attributes) {
+    fn push_run(&mut self, text: String, style: Style) {
         if text.is_empty() {
             return;
         }
 
         if let Some(last) = self.elements.last_mut()
-            && last.attributes == attributes
+            && last.style == style
+            && last.kind == Kind::Text
         {
             last.text.push_str(&text);
             return;
         }
 
-        self.elements.push(Element { text, attributes });
+        self.elements.push(Element::with_style(text, style));
     }
 }
 
@@ -143,6 +348,16 @@ impl Default for RichString {
     }
 }
 
+impl fmt::Display for RichString {
+    /// Writes the text of the [RichString] without any styles, notes or boneyards.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for element in self.text_elements() {
+            f.write_str(&element.text)?;
+        }
+        Ok(())
+    }
+}
+
 impl<T> From<T> for RichString
 where
     T: AsRef<str>,
@@ -157,41 +372,270 @@ where
 /// A [RichString] component, containing a [String] and the style attributes
 /// belonging to said string.
 #[derive(Debug, PartialEq, Eq, Clone, Default, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "ElementRepr", into = "ElementRepr")
+)]
 pub struct Element {
     pub text: String,
-    attributes: Attributes,
+    style: Style,
+    kind: Kind,
+}
+
+/// What an [Element] is a part of.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Hash)]
+enum Kind {
+    /// The text itself.
+    #[default]
+    Text,
+    /// A note, `[[note]]`.
+    Note,
+    /// Cut material, `/* boneyard */`.
+    Boneyard,
 }
 
 impl Element {
     /// Creates a new element based on a [String] with no attributes. Does not parse the
     /// string.
     pub fn new(text: String) -> Self {
+        Self::with_style(text, Style::empty())
+    }
+
+    /// Creates a new element based on a [String] with the given [Style]. Does not parse the
+    /// string.
+    pub fn with_style(text: String, style: Style) -> Self {
         Self {
             text,
-            attributes: Attributes::empty(),
+            style,
+            kind: Kind::Text,
+        }
+    }
+
+    /// Creates a new note element.
+    pub(crate) fn note(text: String) -> Self {
+        Self {
+            kind: Kind::Note,
+            ..Self::new(text)
+        }
+    }
+
+    /// Creates a new boneyard element.
+    pub(crate) fn boneyard(text: String) -> Self {
+        Self {
+            kind: Kind::Boneyard,
+            ..Self::new(text)
         }
     }
 
     /// If the element is styled as bold.
     pub fn is_bold(&self) -> bool {
-        self.attributes.contains(Attributes::BOLD)
+        self.style.contains(Style::BOLD)
     }
 
     /// If the element is styled as underline.
     pub fn is_underline(&self) -> bool {
-        self.attributes.contains(Attributes::UNDERLINE)
+        self.style.contains(Style::UNDERLINE)
     }
 
     /// If the element is styled as italic.
     pub fn is_italic(&self) -> bool {
-        self.attributes.contains(Attributes::ITALIC)
+        self.style.contains(Style::ITALIC)
+    }
+
+    /// The [Style] of the element.
+    pub fn style(&self) -> Style {
+        self.style
+    }
+
+    /// Replaces the [Style] of the element. A note or boneyard stays one.
+    pub fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
+    /// Sets or removes bold for the element.
+    pub fn set_bold(&mut self, bold: bool) {
+        self.style.set(Style::BOLD, bold);
+    }
+
+    /// Sets or removes underline for the element.
+    pub fn set_underline(&mut self, underline: bool) {
+        self.style.set(Style::UNDERLINE, underline);
+    }
+
+    /// Sets or removes italic for the element.
+    pub fn set_italic(&mut self, italic: bool) {
+        self.style.set(Style::ITALIC, italic);
+    }
+
+    /// If the element is a note (`[[note]]`) rather than a part of the text itself.
+    pub fn is_note(&self) -> bool {
+        self.kind == Kind::Note
+    }
+
+    /// If the element is cut material (`/* boneyard */`) rather than a part of the text itself.
+    pub fn is_boneyard(&self) -> bool {
+        self.kind == Kind::Boneyard
+    }
+}
+
+/// The serialized form of an [Element], with its text and only the attributes it has set,
+/// like `{"text": "Hello", "bold": true}`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ElementRepr {
+    text: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    bold: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    underline: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    italic: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    note: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    boneyard: bool,
+}
+
+#[cfg(feature = "serde")]
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+#[cfg(feature = "serde")]
+impl From<Element> for ElementRepr {
+    fn from(element: Element) -> Self {
+        Self {
+            bold: element.is_bold(),
+            underline: element.is_underline(),
+            italic: element.is_italic(),
+            note: element.is_note(),
+            boneyard: element.is_boneyard(),
+            text: element.text,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<ElementRepr> for Element {
+    fn from(repr: ElementRepr) -> Self {
+        let mut style = Style::empty();
+        style.set(Style::BOLD, repr.bold);
+        style.set(Style::UNDERLINE, repr.underline);
+        style.set(Style::ITALIC, repr.italic);
+        let kind = match (repr.note, repr.boneyard) {
+            (true, _) => Kind::Note,
+            (_, true) => Kind::Boneyard,
+            _ => Kind::Text,
+        };
+        Self {
+            text: repr.text,
+            style,
+            kind,
+        }
+    }
+}
+
+/// A part of a line, before the delimiters in it have been matched.
+enum Token {
+    Text(String),
+    /// The index of a [Delimiter].
+    Delimiter(usize),
+    Trivia(Element),
+}
+
+/// A run of `*` or `_` characters that might open or close a style.
+struct Delimiter {
+    marker: u8,
+    /// How many of the characters are not (yet) matched.
+    remaining: usize,
+    can_open: bool,
+    can_close: bool,
+    /// The styles this run opens and closes once matched.
+    opens: Vec<Style>,
+    closes: Vec<Style>,
+}
+
+impl Delimiter {
+    /// Creates a run of `count` `marker`s between the chars `before` and `after`. A run can only
+    /// open a style if it is followed by text, and only close one if it follows text, so that
+    /// e.g. the `*` in `5 * 3 = 15` is never a style. As in CommonMark, a run of `_` within a
+    /// word can neither open nor close, so that e.g. `snake_case` or `jane_doe@example.com`
+    /// are kept as they are.
+    fn new(marker: u8, count: usize, before: Option<char>, after: Option<char>) -> Self {
+        let alphanumeric = |ch: Option<char>| ch.is_some_and(char::is_alphanumeric);
+        let intraword = marker == b'_' && alphanumeric(before) && alphanumeric(after);
+        Self {
+            marker,
+            remaining: count,
+            can_open: !intraword && after.is_some_and(|ch| !ch.is_whitespace()),
+            can_close: !intraword && before.is_some_and(|ch| !ch.is_whitespace()),
+            opens: Vec::new(),
+            closes: Vec::new(),
+        }
+    }
+}
+
+/// Matches every closing [Delimiter] with the nearest open one of the same marker before it.
+///
+/// `*` is matched two at a time as bold while both sides have at least two left, and one at a
+/// time as italic otherwise, so `***` is both. `_` is matched as a single underline. Delimiters
+/// between a matched pair can not be matched anymore, so styles always nest.
+fn match_delimiters(delimiters: &mut [Delimiter]) {
+    let mut openers: Vec<usize> = Vec::new();
+    for closer in 0..delimiters.len() {
+        if delimiters[closer].can_close {
+            while delimiters[closer].remaining > 0 {
+                let marker = delimiters[closer].marker;
+                let Some(pos) = openers
+                    .iter()
+                    .rposition(|&opener| delimiters[opener].marker == marker)
+                else {
+                    break;
+                };
+                let opener = openers[pos];
+
+                let available = delimiters[opener]
+                    .remaining
+                    .min(delimiters[closer].remaining);
+                let (count, style) = match marker {
+                    b'_' => (available, Style::UNDERLINE),
+                    _ if available >= 2 => (2, Style::BOLD),
+                    _ => (1, Style::ITALIC),
+                };
+                delimiters[opener].remaining -= count;
+                delimiters[opener].opens.push(style);
+                delimiters[closer].remaining -= count;
+                delimiters[closer].closes.push(style);
+
+                openers.truncate(pos + 1);
+                if delimiters[opener].remaining == 0 {
+                    openers.pop();
+                }
+            }
+        }
+        if delimiters[closer].can_open && delimiters[closer].remaining > 0 {
+            openers.push(closer);
+        }
     }
 }
 
 bitflags! {
-    /// A bit array keeping track of style attributes for a [RichString].
+    /// The styles of a part of a [RichString], which can be combined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustwell::rich_string::{Element, Style};
+    ///
+    /// let element = Element::with_style("Hello".to_string(), Style::BOLD | Style::ITALIC);
+    ///
+    /// assert!(element.is_bold());
+    /// assert!(element.is_italic());
+    /// assert!(!element.is_underline());
+    /// ```
     #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
-    struct Attributes: u8 {
+    pub struct Style: u8 {
         const BOLD      = 0b001;
         const UNDERLINE = 0b010;
         const ITALIC    = 0b100;
@@ -232,12 +676,12 @@ mod tests {
     #[test]
     fn parses_overlapping_styles() {
         let mut rs = RichString::new();
-        rs.push_str("t_e**x**t_");
+        rs.push_str("t _e**x**t_");
 
         assert!(!rs.elements[0].is_bold());
         assert!(!rs.elements[0].is_italic());
         assert!(!rs.elements[0].is_underline());
-        assert_eq!(rs.elements[0].text, "t".to_string());
+        assert_eq!(rs.elements[0].text, "t ".to_string());
 
         assert!(!rs.elements[1].is_bold());
         assert!(!rs.elements[1].is_italic());
@@ -255,6 +699,24 @@ mod tests {
         assert_eq!(rs.elements[3].text, "t".to_string());
     }
 
+    #[test]
+    fn inserts_notes_without_breaking_styles() {
+        let mut rs = RichString::new();
+        rs.push_str_with_trivia(
+            "**bold text**",
+            vec![(7, Element::note("a note".to_string()))],
+        );
+
+        assert_eq!(rs.elements.len(), 3);
+        assert!(rs.elements[0].is_bold());
+        assert_eq!(rs.elements[0].text, "bold ".to_string());
+        assert!(rs.elements[1].is_note());
+        assert!(!rs.elements[1].is_bold());
+        assert_eq!(rs.elements[1].text, "a note".to_string());
+        assert!(rs.elements[2].is_bold());
+        assert_eq!(rs.elements[2].text, "text".to_string());
+    }
+
     #[test]
     fn parser_ignores_backslash() {
         let mut rs = RichString::new();
@@ -263,4 +725,231 @@ mod tests {
         assert!(!rs.elements[0].is_italic());
         assert_eq!(rs.elements[0].text, "*text*".to_string());
     }
+
+    /// The text and `(bold, italic, underline)` of every element.
+    fn styles(s: &str) -> Vec<(String, (bool, bool, bool))> {
+        RichString::from(s)
+            .elements
+            .into_iter()
+            .map(|e| {
+                let style = (e.is_bold(), e.is_italic(), e.is_underline());
+                (e.text, style)
+            })
+            .collect()
+    }
+
+    const PLAIN: (bool, bool, bool) = (false, false, false);
+    const BOLD: (bool, bool, bool) = (true, false, false);
+    const ITALIC: (bool, bool, bool) = (false, true, false);
+    const UNDERLINE: (bool, bool, bool) = (false, false, true);
+
+    #[test]
+    fn keeps_unmatched_star_literal() {
+        assert_eq!(styles("5 * 3 = 15"), vec![("5 * 3 = 15".into(), PLAIN)]);
+        assert_eq!(
+            styles("5 * 3 = 15 * 1"),
+            vec![("5 * 3 = 15 * 1".into(), PLAIN)]
+        );
+    }
+
+    #[test]
+    fn keeps_lone_underscore_literal() {
+        assert_eq!(
+            styles("Mail john_doe@example.com today."),
+            vec![("Mail john_doe@example.com today.".into(), PLAIN)]
+        );
+    }
+
+    #[test]
+    fn keeps_unclosed_markers_literal() {
+        assert_eq!(styles("**not bold"), vec![("**not bold".into(), PLAIN)]);
+        assert_eq!(styles("*a *"), vec![("*a *".into(), PLAIN)]);
+        assert_eq!(styles("***"), vec![("***".into(), PLAIN)]);
+    }
+
+    #[test]
+    fn does_not_match_across_lines() {
+        assert_eq!(styles("*one\ntwo*"), vec![("*one\ntwo*".into(), PLAIN)]);
+    }
+
+    #[test]
+    fn parses_nested_styles() {
+        assert_eq!(
+            styles("**bold *both* bold**"),
+            vec![
+                ("bold ".into(), BOLD),
+                ("both".into(), (true, true, false)),
+                (" bold".into(), BOLD),
+            ]
+        );
+        assert_eq!(styles("*a *b* c*"), vec![("a b c".into(), ITALIC)]);
+    }
+
+    #[test]
+    fn parses_bold_italic() {
+        assert_eq!(
+            styles("***both***"),
+            vec![("both".into(), (true, true, false))]
+        );
+        assert_eq!(
+            styles("***both** italic*"),
+            vec![
+                ("both".into(), (true, true, false)),
+                (" italic".into(), ITALIC),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_leftover_markers_literal() {
+        assert_eq!(
+            styles("**a*"),
+            vec![("*".into(), PLAIN), ("a".into(), ITALIC)]
+        );
+        assert_eq!(
+            styles("*a**"),
+            vec![("a".into(), ITALIC), ("*".into(), PLAIN)]
+        );
+    }
+
+    #[test]
+    fn keeps_underscores_within_words() {
+        let line = "Send it to john_doe and jane_doe today.";
+        assert_eq!(styles(line), vec![(line.into(), PLAIN)]);
+
+        let line = "Mail snake_case@example.com or camel_case@example.com";
+        assert_eq!(styles(line), vec![(line.into(), PLAIN)]);
+
+        assert_eq!(
+            styles("_john_doe_ and *jane_doe*"),
+            vec![
+                ("john_doe".into(), UNDERLINE),
+                (" and ".into(), PLAIN),
+                ("jane_doe".into(), ITALIC),
+            ]
+        );
+    }
+
+    #[test]
+    fn does_not_cross_styles() {
+        assert_eq!(
+            styles("*a _b* c_"),
+            vec![("a _b".into(), ITALIC), (" c_".into(), PLAIN)]
+        );
+    }
+
+    #[test]
+    fn keeps_trailing_backslash() {
+        assert_eq!(styles(r"end\"), vec![(r"end\".into(), PLAIN)]);
+        assert_eq!(
+            styles(r"\**not bold**"),
+            vec![
+                ("*".into(), PLAIN),
+                ("not bold".into(), ITALIC),
+                ("*".into(), PLAIN)
+            ]
+        );
+    }
+
+    #[test]
+    fn pushes_styled_text_unparsed() {
+        let mut rs = RichString::new();
+        rs.push_styled("_one_\ntwo", Style::BOLD | Style::UNDERLINE);
+
+        assert_eq!(rs.elements.len(), 3);
+        assert_eq!(rs.elements[0].text, "_one_".to_string());
+        assert_eq!(rs.elements[0].style(), Style::BOLD | Style::UNDERLINE);
+        assert_eq!(rs.elements[1].style(), Style::empty());
+        assert_eq!(rs.elements[2].text, "two".to_string());
+        assert!(rs.elements[2].is_bold());
+    }
+
+    #[test]
+    fn sets_styles_of_notes() {
+        let mut element = Element::note("a note".to_string());
+        element.set_style(Style::ITALIC);
+        element.set_bold(true);
+
+        assert!(element.is_note());
+        assert_eq!(element.style(), Style::BOLD | Style::ITALIC);
+    }
+
+    #[test]
+    fn counts_and_displays_text_only() {
+        let mut rs = RichString::from("**Grüß** dich");
+        rs.push_trivia(Element::note("a note".to_string()));
+
+        assert_eq!(rs.len(), 9);
+        assert_eq!(rs.to_string(), "Grüß dich".to_string());
+        assert_eq!(rs.plain_text(), rs.to_string());
+        assert!(!rs.is_empty());
+
+        let mut note = RichString::new();
+        note.push_trivia(Element::note("only a note".to_string()));
+        assert_eq!(note.elements.len(), 1);
+        assert_eq!(note.len(), 0);
+        assert!(note.is_empty());
+        assert_eq!(note.to_string(), String::new());
+        assert_eq!(note.slice(0..0), Some(note.clone()));
+    }
+
+    #[test]
+    fn slices_keeping_styles() {
+        let rs = RichString::from("a**bcd**_e_");
+
+        assert_eq!(
+            rs.slice(2..).unwrap().elements,
+            vec![
+                Element::with_style("cd".to_string(), Style::BOLD),
+                Element::with_style("e".to_string(), Style::UNDERLINE),
+            ]
+        );
+        assert_eq!(
+            rs.slice(..=1).unwrap().elements,
+            vec![
+                Element::new("a".to_string()),
+                Element::with_style("b".to_string(), Style::BOLD),
+            ]
+        );
+        assert_eq!(rs.slice(1..1).unwrap().elements, vec![]);
+    }
+
+    #[test]
+    fn splits_keeping_trivia_once() {
+        let mut rs = RichString::new();
+        rs.push_str_with_trivia(
+            "one two",
+            vec![
+                (3, Element::note("middle".to_string())),
+                (7, Element::boneyard("end".to_string())),
+            ],
+        );
+
+        let (left, right) = rs.split_at(3).unwrap();
+        assert_eq!(left.elements, vec![Element::new("one".to_string())]);
+        assert!(right.elements[0].is_note());
+        assert_eq!(right.to_string(), " two".to_string());
+        assert!(right.elements[2].is_boneyard());
+
+        let (left, right) = rs.split_at(7).unwrap();
+        assert_eq!(left.elements.len(), 3);
+        assert_eq!(right.elements.len(), 1);
+    }
+
+    #[test]
+    fn fails_to_slice_out_of_bounds() {
+        let rs = RichString::from("short");
+
+        assert_eq!(rs.slice(2..6), None);
+        assert_eq!(rs.slice((Bound::Included(3), Bound::Excluded(2))), None);
+        assert_eq!(rs.slice(..=usize::MAX), None);
+        assert_eq!(rs.split_at(6), None);
+        assert_eq!(rs.slice(..), Some(rs.clone()));
+    }
+
+    #[test]
+    fn counts_escaped_whitespace_as_text() {
+        assert_eq!(styles(r"**bold\ **"), vec![("bold ".into(), BOLD)]);
+        assert_eq!(styles(r"*\ italic*"), vec![(" italic".into(), ITALIC)]);
+    }
 }
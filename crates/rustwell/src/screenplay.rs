@@ -1,50 +1,196 @@
 //! This module implements the [Screenplay] AST with all the components of a screenplay, to be
 //! easily exported to any format.
+//!
+//! # Serialization
+//!
+//! With the `serde` feature, the whole AST can be serialized and deserialized with
+//! [serde](https://serde.rs/). The shape is kept stable, so that it can be read by tools not
+//! written in Rust. As JSON, it looks as follows:
+//!
+//! - A [Screenplay] has its `titlepage`, which is `null` if there is none, and its `elements`.
+//! - Every [Spanned] node has a `span` next to its own fields, with the `start` and `end`
+//!   [Position](crate::span::Position)s as `offset`, `line` and `column`. A node without a
+//!   `span` is read with a default one.
+//! - An [Element] or a [DialogueElement] has its kind as `type` in snake case, like `heading` or
+//!   `page_break`, and its contents as `content`. Variants with named fields have them as an
+//!   object, [Element::DualDialogue] has its two [Dialogue]s as an array and
+//!   [Element::PageBreak] has no `content`.
+//! - A [RichString] is an array of runs, each with its `text` and any of `bold`, `underline`,
+//!   `italic`, `note` and `boneyard` that are set. `note` and `boneyard` mark notes and boneyards
+//!   within the text of another element. The content of an [Element::Note] is the note's text
+//!   itself, so its runs are plain text runs.
+//! - A [TitlePage] has every field as an array of [RichString]s, with `extra` as an array of
+//!   `[key, values]` pairs. Missing fields are read as empty.
+//!
+//! ```json
+//! {
+//!   "titlepage": null,
+//!   "elements": [
+//!     {
+//!       "type": "heading",
+//!       "content": { "slug": [{ "text": "INT. HOUSE - DAY" }], "number": null },
+//!       "span": {
+//!         "start": { "offset": 0, "line": 1, "column": 1 },
+//!         "end": { "offset": 16, "line": 1, "column": 17 }
+//!       }
+//!     },
+//!     {
+//!       "type": "dialogue",
+//!       "content": {
+//!         "character": [{ "text": "ALICE" }],
+//!         "extension": null,
+//!         "elements": [{ "type": "line", "content": [{ "text": "Hi!", "bold": true }] }]
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
 
 use crate::rich_string::RichString;
+use crate::span::Spanned;
+
+mod numbering;
+mod scene;
+pub mod visit;
+
+pub use scene::Scene;
+pub use visit::{Visit, VisitMut};
 
 /// A (very flat) abstract syntax tree consisting of the entirety of a screenplay and well as the
 /// information for the title page of the screenplay.
 ///
 /// Contains both a [`Option<TitlePage>`] and a [`Vec<Element>`], which are the screenplay components.
+/// Every node is [Spanned] with where it was found in the source it was parsed from. See
+/// [Screenplay::scenes] for the elements grouped into scenes.
 #[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Screenplay {
-    pub titlepage: Option<TitlePage>,
-    pub elements: Vec<Element>,
+    pub titlepage: Option<Spanned<TitlePage>>,
+    pub elements: Vec<Spanned<Element>>,
 }
 
 impl Screenplay {
-    /// Create a new [Screenplay].
+    /// Create a new [Screenplay]. As the components are not parsed from any source, they will
+    /// all be given a default [Span](crate::span::Span).
     pub fn new(titlepage: Option<TitlePage>, elements: Vec<Element>) -> Self {
         Self {
-            titlepage,
-            elements,
+            titlepage: titlepage.map(Spanned::from),
+            elements: elements.into_iter().map(Spanned::from).collect(),
         }
     }
 
     /// Set the [TitlePage] on a [Screenplay].
     pub fn set_titlepage(&mut self, titlepage: Option<TitlePage>) {
-        self.titlepage = titlepage;
+        self.titlepage = titlepage.map(Spanned::from);
     }
 }
 
 /// The components of a [Screenplay], like scene headings, action, dialogue, etc.
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", content = "content", rename_all = "snake_case")
+)]
 pub enum Element {
+    /// A section, like an act or a sequence, marking the structure of the screenplay. The
+    /// `depth` is the number of `#` the section was written with, so `# Act` has depth 1.
+    /// Sections are not a part of the printed screenplay.
+    Section {
+        depth: usize,
+        title: RichString,
+    },
+    /// A scene heading. See [Element::scene_heading] for the parts of the `slug`.
     Heading {
         slug: RichString,
         number: Option<String>,
     },
     Action(RichString),
     Dialogue(Dialogue),
-    DualDialogue(Dialogue, Dialogue),
+    DualDialogue(Spanned<Dialogue>, Spanned<Dialogue>),
     Lyrics(RichString),
     Transition(RichString),
     CenteredText(RichString),
     Synopsis(RichString),
+    /// A note (`[[note]]`) standing on its own, rather than within the text of another element.
+    /// Notes are not a part of the printed screenplay by default. The [RichString] holds the text
+    /// of the note as plain text, not as a note within it.
+    Note(RichString),
+    /// Cut material (`/* boneyard */`) standing on its own, kept exactly as written. Boneyards
+    /// are only kept when parsing with [ParseOptions::boneyards](crate::ParseOptions::boneyards).
+    Boneyard(String),
     PageBreak,
 }
 
+impl Element {
+    /// The structured parts of a scene heading, if the [Element] is one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustwell::parse;
+    /// use rustwell::screenplay::Setting;
+    ///
+    /// let screenplay = parse("INT./EXT. CAR - MOVING - NIGHT\n\nThey drive.");
+    /// let heading = screenplay.elements[0].scene_heading().unwrap();
+    ///
+    /// assert_eq!(heading.setting, Some(Setting::InteriorExterior));
+    /// assert_eq!(heading.location, "CAR");
+    /// assert_eq!(heading.sub_location.as_deref(), Some("MOVING"));
+    /// assert_eq!(heading.time.as_deref(), Some("NIGHT"));
+    /// ```
+    pub fn scene_heading(&self) -> Option<SceneHeading> {
+        match self {
+            Element::Heading { slug, .. } => Some(SceneHeading::parse(slug)),
+            _ => None,
+        }
+    }
+}
+
+/// The parts of the slug of a scene heading, like `INT. HOUSE - KITCHEN - DAY`, as plain text
+/// without styling or notes.
+///
+/// Slugs that do not follow the pattern, like forced headings (`.FLASHBACK`), fall back to
+/// keeping everything that could not be placed in `location`.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SceneHeading {
+    /// `INT.`, `EXT.` and the like, if the slug starts with one.
+    pub setting: Option<Setting>,
+    pub location: String,
+    /// Any parts between the location and the time of day, like `KITCHEN`.
+    pub sub_location: Option<String>,
+    /// The time of day, like `DAY`, `NIGHT` or `CONTINUOUS`.
+    pub time: Option<String>,
+}
+
+impl SceneHeading {
+    /// Splits the slug of a scene heading into its parts.
+    pub fn parse(slug: &RichString) -> Self {
+        crate::parser::parse_scene_heading(slug)
+    }
+}
+
+/// Where a scene takes place, as written at the start of a scene heading.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Setting {
+    /// `INT.`
+    Interior,
+    /// `EXT.`
+    Exterior,
+    /// `INT./EXT.` or `I/E`
+    InteriorExterior,
+    /// `EXT./INT.` or `E/I`
+    ExteriorInterior,
+    /// `EST.`, an establishing shot.
+    Establishing,
+}
+
 /// Dialogue consisting of a character name, an extension, parentheticals and lines.
 /// A single [Dialogue] can have multiple parentheticals and lines.
 ///
@@ -52,10 +198,11 @@ pub enum Element {
 /// (parenthetical)
 /// Line.
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Dialogue {
     pub character: RichString,
     pub extension: Option<RichString>,
-    pub elements: Vec<DialogueElement>,
+    pub elements: Vec<Spanned<DialogueElement>>,
 }
 
 impl Dialogue {
@@ -76,6 +223,11 @@ impl Default for Dialogue {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", content = "content", rename_all = "snake_case")
+)]
 pub enum DialogueElement {
     Parenthetical(RichString),
     Line(RichString),
@@ -83,7 +235,19 @@ pub enum DialogueElement {
 
 /// The information for a title page. Each field may be empty as none are strictly required
 /// according to the fountain specification.
+///
+/// Keys other than the ones with fields of their own, like `Notes:`, `Copyright:` or
+/// `Episode:`, are kept in `extra` in the order they were written. Together, the fields and
+/// `extra` act as an ordered map of keys to values, see [TitlePage::get] and [TitlePage::insert].
+///
+/// Values are parsed without the whitespace around them, so `Title:   Big Fish` has the title
+/// `Big Fish`.
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct TitlePage {
     pub title: Vec<RichString>,
     pub credit: Vec<RichString>,
@@ -91,6 +255,8 @@ pub struct TitlePage {
     pub source: Vec<RichString>,
     pub draft_date: Vec<RichString>,
     pub contact: Vec<RichString>,
+    /// All other keys with their values, in order. Keys are kept as they were written.
+    pub extra: Vec<(String, Vec<RichString>)>,
 }
 
 impl TitlePage {
@@ -103,6 +269,105 @@ impl TitlePage {
             source: Vec::new(),
             draft_date: Vec::new(),
             contact: Vec::new(),
+            extra: Vec::new(),
+        }
+    }
+
+    /// The values of a key, ignoring case, or [None] if it has none. Both the keys with fields
+    /// of their own and the `extra` keys can be looked up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustwell::screenplay::TitlePage;
+    ///
+    /// let mut titlepage = TitlePage::new();
+    /// titlepage.insert("Copyright", vec!["(c) 2025".into()]);
+    /// titlepage.insert("Title", vec!["Big Fish".into()]);
+    ///
+    /// assert_eq!(titlepage.get("COPYRIGHT"), Some(&["(c) 2025".into()][..]));
+    /// assert_eq!(titlepage.title, vec!["Big Fish".into()]);
+    /// assert_eq!(titlepage.get("Notes"), None);
+    /// assert_eq!(titlepage.get("Author"), None);
+    /// ```
+    pub fn get(&self, key: &str) -> Option<&[RichString]> {
+        let values = match self.field(key) {
+            Some(values) => Some(values),
+            None => self
+                .extra
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key.trim()))
+                .map(|(_, values)| values),
+        };
+        values
+            .map(Vec::as_slice)
+            .filter(|values| !values.is_empty())
+    }
+
+    /// Sets the values of a key, ignoring case. Keys with fields of their own set the field,
+    /// while other keys replace the values of an earlier equal key in `extra` or are added
+    /// last.
+    pub fn insert(&mut self, key: impl Into<String>, values: Vec<RichString>) {
+        let key = key.into();
+        if let Some(field) = self.field_mut(&key) {
+            *field = values;
+        } else if let Some((_, old)) = self
+            .extra
+            .iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key.trim()))
+        {
+            *old = values;
+        } else {
+            self.extra.push((key.trim().to_string(), values));
+        }
+    }
+
+    /// Removes the values of a key, ignoring case, and returns them if there were any. Keys
+    /// with fields of their own have their field emptied.
+    pub fn remove(&mut self, key: &str) -> Option<Vec<RichString>> {
+        if let Some(field) = self.field_mut(key) {
+            return Some(std::mem::take(field)).filter(|values| !values.is_empty());
+        }
+
+        let i = self
+            .extra
+            .iter()
+            .position(|(k, _)| k.eq_ignore_ascii_case(key.trim()))?;
+        Some(self.extra.remove(i).1)
+    }
+
+    /// If the [TitlePage] has no values at all.
+    pub fn is_empty(&self) -> bool {
+        self.title.is_empty()
+            && self.credit.is_empty()
+            && self.authors.is_empty()
+            && self.source.is_empty()
+            && self.draft_date.is_empty()
+            && self.contact.is_empty()
+            && self.extra.is_empty()
+    }
+
+    fn field(&self, key: &str) -> Option<&Vec<RichString>> {
+        match key.trim().to_ascii_uppercase().as_str() {
+            "TITLE" => Some(&self.title),
+            "CREDIT" => Some(&self.credit),
+            "AUTHOR" | "AUTHORS" => Some(&self.authors),
+            "SOURCE" => Some(&self.source),
+            "DRAFT DATE" => Some(&self.draft_date),
+            "CONTACT" => Some(&self.contact),
+            _ => None,
+        }
+    }
+
+    fn field_mut(&mut self, key: &str) -> Option<&mut Vec<RichString>> {
+        match key.trim().to_ascii_uppercase().as_str() {
+            "TITLE" => Some(&mut self.title),
+            "CREDIT" => Some(&mut self.credit),
+            "AUTHOR" | "AUTHORS" => Some(&mut self.authors),
+            "SOURCE" => Some(&mut self.source),
+            "DRAFT DATE" => Some(&mut self.draft_date),
+            "CONTACT" => Some(&mut self.contact),
+            _ => None,
         }
     }
 }
@@ -112,3 +377,128 @@ impl Default for TitlePage {
         Self::new()
     }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::ParseOptions;
+
+    #[test]
+    fn serializes_stable_shape() {
+        let screenplay = Screenplay::new(
+            None,
+            vec![
+                Element::Heading {
+                    slug: "INT. HOUSE - DAY".into(),
+                    number: Some("1".to_string()),
+                },
+                Element::Action("It is *dark*.".into()),
+                Element::PageBreak,
+            ],
+        );
+        let json = serde_json::to_value(&screenplay).unwrap();
+
+        let span = serde_json::json!({
+            "start": { "offset": 0, "line": 0, "column": 0 },
+            "end": { "offset": 0, "line": 0, "column": 0 },
+        });
+        let expected = serde_json::json!({
+            "titlepage": null,
+            "elements": [
+                {
+                    "type": "heading",
+                    "content": { "slug": [{ "text": "INT. HOUSE - DAY" }], "number": "1" },
+                    "span": span,
+                },
+                {
+                    "type": "action",
+                    "content": [
+                        { "text": "It is " },
+                        { "text": "dark", "italic": true },
+                        { "text": "." },
+                    ],
+                    "span": span,
+                },
+                { "type": "page_break", "span": span },
+            ],
+        });
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn reads_without_spans() {
+        let json = r#"{
+            "elements": [
+                {
+                    "type": "dual_dialogue",
+                    "content": [
+                        { "character": [{ "text": "ALICE" }], "elements": [] },
+                        {
+                            "character": [{ "text": "BOB" }],
+                            "extension": [{ "text": "V.O." }],
+                            "elements": [{ "type": "line", "content": [{ "text": "Hi" }] }]
+                        }
+                    ]
+                },
+                { "type": "note", "content": [{ "text": "check" }] }
+            ]
+        }"#;
+        let screenplay: Screenplay = serde_json::from_str(json).unwrap();
+
+        let mut bob = Dialogue::new();
+        bob.character = "BOB".into();
+        bob.extension = Some("V.O.".into());
+        bob.elements.push(DialogueElement::Line("Hi".into()).into());
+        let mut alice = Dialogue::new();
+        alice.character = "ALICE".into();
+        let expected = Screenplay::new(
+            None,
+            vec![
+                Element::DualDialogue(alice.into(), bob.into()),
+                Element::Note("check".into()),
+            ],
+        );
+        assert_eq!(screenplay, expected);
+    }
+
+    #[test]
+    fn writes_notes_as_plain_text() {
+        let screenplay = crate::parse("[[check]]");
+        let json = serde_json::to_value(&screenplay).unwrap();
+        assert_eq!(
+            json["elements"][0]["content"],
+            serde_json::json!([{ "text": "check" }])
+        );
+
+        let read: Screenplay = serde_json::from_value(json).unwrap();
+        assert_eq!(read, screenplay);
+        assert_eq!(
+            read,
+            Screenplay::new(None, vec![Element::Note("check".into())])
+        );
+    }
+
+    #[test]
+    fn round_trips_parsed_screenplay() {
+        let src = "Title: **Big** Fish\nNotes: Draft\n\nINT. HOUSE - DAY #1#\n\n\
+                   ALICE (V.O.)\n(quietly)\nHello [[a note]] _there_.\n\n\
+                   BOB ^\nHi.\n\n/* cut */\n\n===\n\n> THE END <";
+        let screenplay = crate::parse_with_options(
+            src,
+            ParseOptions {
+                boneyards: true,
+                ..Default::default()
+            },
+        );
+
+        let json = serde_json::to_string(&screenplay).unwrap();
+        let read: Screenplay = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(read, screenplay);
+        assert_eq!(read.elements[1].span, screenplay.elements[1].span);
+        assert_eq!(
+            read.titlepage.unwrap().span,
+            screenplay.titlepage.unwrap().span
+        );
+    }
+}
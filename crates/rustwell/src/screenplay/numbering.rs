@@ -0,0 +1,494 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::screenplay::{Element, Screenplay, TitlePage};
+use crate::span::Spanned;
+
+/// The title page key recording that the scene numbers are locked, as `Scene Numbers: Locked`.
+const LOCK_KEY: &str = "Scene Numbers";
+const LOCKED: &str = "Locked";
+
+/// The slug of the placeholder left in place of an omitted scene.
+const OMITTED: &str = "OMITTED";
+
+impl Screenplay {
+    /// If the scene numbers are locked, as recorded by `Scene Numbers: Locked` on the title
+    /// page. See [Screenplay::lock_scene_numbers].
+    pub fn scene_numbers_locked(&self) -> bool {
+        self.titlepage
+            .as_ref()
+            .and_then(|titlepage| titlepage.get(LOCK_KEY))
+            .is_some_and(|values| {
+                values
+                    .iter()
+                    .any(|v| v.plain_text().trim().eq_ignore_ascii_case(LOCKED))
+            })
+    }
+
+    /// Numbers all scenes.
+    ///
+    /// Unless the scene numbers are locked, every scene is numbered from 1 and up, replacing
+    /// any numbers written before. Once locked, numbered scenes keep their numbers, while new
+    /// scenes are numbered production style between the scenes around them, so that a scene
+    /// after scene 12 becomes `12A`, and a scene before the first one, scene 1, becomes `A1`.
+    /// Any whole number missing between the locked numbers, like a deleted scene 13 between 12
+    /// and 14, gets an `OMITTED` placeholder. As a deleted first or last scene leaves no such
+    /// gap, scenes are better removed with [Screenplay::omit_scene] once locked.
+    ///
+    /// The numbers always stay in order. Returns the indices of the new scenes that no number
+    /// fits for, which are left without one, like a scene between scenes `12` and `12A`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustwell::parse;
+    ///
+    /// let mut screenplay = parse("INT. HOUSE - DAY\n\nEXT. GARDEN - DAY\n\nINT. CAR - NIGHT");
+    /// screenplay.lock_scene_numbers();
+    ///
+    /// let scene = parse("EXT. STREET - NIGHT").elements;
+    /// screenplay.insert_scene(1, scene);
+    /// screenplay.remove_scene(2);
+    /// let unnumbered = screenplay.number_scenes();
+    ///
+    /// let numbers: Vec<_> = screenplay.scenes().iter().map(|s| s.number.unwrap()).collect();
+    /// assert_eq!(numbers, ["1", "1A", "2", "3"]);
+    /// assert!(unnumbered.is_empty());
+    /// assert_eq!(screenplay.scenes()[2].heading.plain_text(), "OMITTED");
+    /// ```
+    pub fn number_scenes(&mut self) -> Vec<usize> {
+        let numbered = |n: Option<&str>| n.is_some_and(|n| n.parse::<Number>().is_ok());
+        if !self.scene_numbers_locked() || !self.numbers().any(numbered) {
+            for (i, number) in self.numbers_mut().enumerate() {
+                *number = Some((i + 1).to_string());
+            }
+            return Vec::new();
+        }
+
+        let unnumbered = self.number_new_scenes();
+        self.insert_omitted(unnumbered)
+    }
+
+    /// Numbers all scenes, see [Screenplay::number_scenes], and locks the numbers by recording
+    /// `Scene Numbers: Locked` on the title page, adding a title page if there is none. Returns
+    /// the indices of the scenes left without a number, like [Screenplay::number_scenes].
+    pub fn lock_scene_numbers(&mut self) -> Vec<usize> {
+        self.titlepage
+            .get_or_insert_with(|| Spanned::from(TitlePage::new()))
+            .insert(LOCK_KEY, vec![LOCKED.into()]);
+        self.number_scenes()
+    }
+
+    /// Unlocks the scene numbers, removing the record of the lock from the title page, and the
+    /// title page itself if nothing else is left on it. The numbers themselves are kept.
+    pub fn unlock_scene_numbers(&mut self) {
+        if let Some(titlepage) = &mut self.titlepage {
+            titlepage.remove(LOCK_KEY);
+            if titlepage.is_empty() {
+                self.titlepage = None;
+            }
+        }
+    }
+
+    /// Replaces scene `index` with an `OMITTED` placeholder keeping its number, for removing a
+    /// scene once the scene numbers are locked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn omit_scene(&mut self, index: usize) {
+        let range = self.scene_ranges()[index].clone();
+        let number = match &self.elements[range.start].node {
+            Element::Heading { number, .. } => number.clone(),
+            _ => unreachable!("a scene starts with a scene heading"),
+        };
+        let placeholder = Element::Heading {
+            slug: OMITTED.into(),
+            number,
+        };
+        self.elements.splice(range, [placeholder.into()]);
+    }
+
+    /// Numbers the scenes without a number between the numbered scenes before and after them,
+    /// returning the indices of the scenes no number fits for.
+    fn number_new_scenes(&mut self) -> Vec<usize> {
+        let mut used: HashSet<String> = self.numbers().flatten().map(String::from).collect();
+        let parsed: Vec<Option<Number>> = self
+            .numbers()
+            .map(|n| n.and_then(|n| n.parse().ok()))
+            .collect();
+
+        let mut unnumbered = Vec::new();
+        let mut previous: Option<Number> = None;
+        for (i, number) in self.numbers_mut().enumerate() {
+            if number.is_some() {
+                previous = parsed[i].clone().or(previous);
+                continue;
+            }
+
+            let upcoming = parsed[i..].iter().flatten().next();
+            match Number::between(previous.as_ref(), upcoming, &used) {
+                Some(next) => {
+                    used.insert(next.to_string());
+                    *number = Some(next.to_string());
+                    previous = Some(next);
+                }
+                None => unnumbered.push(i),
+            }
+        }
+        unnumbered
+    }
+
+    /// Inserts an `OMITTED` placeholder for every whole number missing between the scene
+    /// numbers, right before the first scene numbered after it. Returns the indices of the
+    /// `unnumbered` scenes with the placeholders counted in.
+    fn insert_omitted(&mut self, unnumbered: Vec<usize>) -> Vec<usize> {
+        let whole: HashSet<usize> = self
+            .numbers()
+            .flatten()
+            .flat_map(str::parse::<Number>)
+            .filter(|n| n.prefix.is_empty() && n.suffix.is_empty())
+            .map(|n| n.number)
+            .collect();
+
+        // The highest whole number that comes before the scenes seen so far, and the index each
+        // scene is moved to by the placeholders before it
+        let mut highest: Option<usize> = None;
+        let mut moved = Vec::new();
+        let mut scenes = 0;
+        let mut elements = Vec::with_capacity(self.elements.len());
+        for element in std::mem::take(&mut self.elements) {
+            if let Element::Heading { number, .. } = &element.node {
+                if let Some(number) = number.as_deref().and_then(|n| n.parse::<Number>().ok()) {
+                    let first = highest.map_or(number.number, |highest| highest + 1);
+                    let missing = (first..=number.number)
+                        .filter(|n| !whole.contains(n))
+                        .map(Number::from)
+                        .take_while(|missing| *missing < number);
+                    for missing in missing {
+                        let placeholder = Element::Heading {
+                            slug: OMITTED.into(),
+                            number: Some(missing.to_string()),
+                        };
+                        elements.push(placeholder.into());
+                        scenes += 1;
+                    }
+
+                    // A prefixed number like `A12` still comes before `12`
+                    let before = match number.prefix.is_empty() {
+                        true => number.number,
+                        false => number.number.saturating_sub(1),
+                    };
+                    highest = highest.max(Some(before));
+                }
+                moved.push(scenes);
+                scenes += 1;
+            }
+            elements.push(element);
+        }
+        self.elements = elements;
+        unnumbered.into_iter().map(|i| moved[i]).collect()
+    }
+
+    /// The numbers of all scenes, in order.
+    fn numbers(&self) -> impl Iterator<Item = Option<&str>> {
+        self.elements.iter().filter_map(|e| match &e.node {
+            Element::Heading { number, .. } => Some(number.as_deref()),
+            _ => None,
+        })
+    }
+
+    /// The numbers of all scenes, in order, to be changed.
+    fn numbers_mut(&mut self) -> impl Iterator<Item = &mut Option<String>> {
+        self.elements.iter_mut().filter_map(|e| match &mut e.node {
+            Element::Heading { number, .. } => Some(number),
+            _ => None,
+        })
+    }
+}
+
+/// A production style scene number, like `12`, `12A` or `A12`.
+///
+/// Numbers are ordered as they are in a screenplay, so `A12` comes before `12`, which comes
+/// before `12A`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Number {
+    prefix: String,
+    number: usize,
+    suffix: String,
+}
+
+impl Number {
+    /// The number of a scene inserted right after this one, like `12A` after `12` and `B12`
+    /// after `A12`.
+    fn next(&self) -> Self {
+        let mut next = self.clone();
+        if next.prefix.is_empty() {
+            next.suffix = next_letters(&next.suffix);
+        } else {
+            next.prefix = next_letters(&next.prefix);
+        }
+        next
+    }
+
+    /// The first number after `lower` and before `upper` that is not `used`, if any fits. A
+    /// number is looked for after `lower` first, like `12A` after `12`, and then before
+    /// `upper`, like `A12` before `12`.
+    fn between(lower: Option<&Self>, upper: Option<&Self>, used: &HashSet<String>) -> Option<Self> {
+        let free = |n: &Self| !used.contains(&n.to_string());
+        if let Some(lower) = lower {
+            let mut next = lower.next();
+            while !free(&next) {
+                next = next.next();
+            }
+            if upper.is_none_or(|upper| next < *upper) {
+                return Some(next);
+            }
+        }
+
+        // Every number before `upper` with a prefix comes after `lower`, unless `lower` has
+        // the same number, in which case the one after `lower` was already tried
+        let upper = upper?;
+        if lower.is_some_and(|lower| lower.number >= upper.number) {
+            return None;
+        }
+        let mut next = Self {
+            prefix: "A".to_string(),
+            number: upper.number,
+            suffix: String::new(),
+        };
+        while next < *upper {
+            if free(&next) {
+                return Some(next);
+            }
+            next = next.next();
+        }
+        None
+    }
+}
+
+impl From<usize> for Number {
+    fn from(number: usize) -> Self {
+        Self {
+            prefix: String::new(),
+            number,
+            suffix: String::new(),
+        }
+    }
+}
+
+impl std::str::FromStr for Number {
+    type Err = ();
+
+    /// Parses a number made up of letters, digits and letters, where only one of the two runs
+    /// of letters may be given.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_uppercase();
+        let digits = s.find(|c: char| c.is_ascii_digit()).ok_or(())?;
+        let (prefix, rest) = s.split_at(digits);
+        let letters = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (number, suffix) = rest.split_at(letters);
+
+        let is_letters = |s: &str| s.chars().all(|c| c.is_ascii_uppercase());
+        if !is_letters(prefix) || !is_letters(suffix) || !(prefix.is_empty() || suffix.is_empty()) {
+            return Err(());
+        }
+
+        Ok(Self {
+            prefix: prefix.to_string(),
+            number: number.parse().map_err(|_| ())?,
+            suffix: suffix.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.prefix, self.number, self.suffix)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // Prefixed numbers come before the whole number, and suffixed ones after it
+        let key = |n: &Self| {
+            let place = match (n.prefix.is_empty(), n.suffix.is_empty()) {
+                (false, _) => 0,
+                (true, true) => 1,
+                (true, false) => 2,
+            };
+            let letters = if n.prefix.is_empty() {
+                &n.suffix
+            } else {
+                &n.prefix
+            };
+            (n.number, place, letters.len(), letters.clone())
+        };
+        key(self).cmp(&key(other))
+    }
+}
+
+/// The letters following `letters`, counting `A` to `Z` and then `AA`, `AB` and so on.
+fn next_letters(letters: &str) -> String {
+    let mut next: Vec<char> = letters.chars().collect();
+    for c in next.iter_mut().rev() {
+        if *c == 'Z' {
+            *c = 'A';
+        } else {
+            *c = char::from_u32(*c as u32 + 1).unwrap_or('A');
+            return next.into_iter().collect();
+        }
+    }
+    next.insert(0, 'A');
+    next.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn numbers(screenplay: &Screenplay) -> Vec<String> {
+        screenplay
+            .scenes()
+            .iter()
+            .map(|s| format!("{} {}", s.number.unwrap_or("-"), s.heading.plain_text()))
+            .collect()
+    }
+
+    #[test]
+    fn numbers_unlocked_scenes_in_order() {
+        let mut screenplay = parse("INT. A #7#\n\nINT. B\n\nINT. C #2A#");
+        screenplay.number_scenes();
+
+        assert_eq!(numbers(&screenplay), ["1 INT. A", "2 INT. B", "3 INT. C"]);
+        assert!(!screenplay.scene_numbers_locked());
+    }
+
+    #[test]
+    fn numbers_new_scenes_after_locking() {
+        let src = "Title: Fish\nScene Numbers: Locked\n\nINT. NEW\n\nINT. NEWER\n\nINT. A #1#\n\n\
+                   INT. B #2#\n\nINT. C #2A#\n\nINT. D\n\nINT. E\n\nINT. F #3#\n\nINT. G";
+        let mut screenplay = parse(src);
+        assert!(screenplay.scene_numbers_locked());
+        assert!(screenplay.number_scenes().is_empty());
+
+        let expected = [
+            "A1 INT. NEW",
+            "B1 INT. NEWER",
+            "1 INT. A",
+            "2 INT. B",
+            "2A INT. C",
+            "2B INT. D",
+            "2C INT. E",
+            "3 INT. F",
+            "3A INT. G",
+        ];
+        assert_eq!(numbers(&screenplay), expected);
+    }
+
+    #[test]
+    fn keeps_numbers_in_order() {
+        let src = "Scene Numbers: Locked\n\nINT. A #1#\n\nINT. B #2#\n\nINT. C\n\nINT. D\n\n\
+                   INT. E #2A#\n\nINT. F #2B#\n\nINT. G\n\nINT. H #2C#\n\nINT. I #4#";
+        let mut screenplay = parse(src);
+
+        assert_eq!(screenplay.number_scenes(), [2, 3, 6]);
+        let expected = [
+            "1 INT. A",
+            "2 INT. B",
+            "- INT. C",
+            "- INT. D",
+            "2A INT. E",
+            "2B INT. F",
+            "- INT. G",
+            "2C INT. H",
+            "3 OMITTED",
+            "4 INT. I",
+        ];
+        assert_eq!(numbers(&screenplay), expected);
+
+        let src = "Scene Numbers: Locked\n\nINT. A #1#\n\nINT. B #3#\n\nINT. C #3A#\n\n\
+                   INT. D\n\nINT. E #3B#";
+        let mut screenplay = parse(src);
+        assert_eq!(screenplay.number_scenes(), [4]);
+        assert_eq!(screenplay.scenes()[4].heading.plain_text(), "INT. D");
+    }
+
+    #[test]
+    fn leaves_omitted_placeholders() {
+        let mut screenplay = parse("INT. A\n\nINT. B\n\nINT. C\n\nINT. D\n\nINT. E");
+        screenplay.lock_scene_numbers();
+        screenplay.omit_scene(1);
+        screenplay.remove_scene(3);
+        screenplay.remove_scene(2);
+        screenplay.number_scenes();
+
+        let expected = [
+            "1 INT. A",
+            "2 OMITTED",
+            "3 OMITTED",
+            "4 OMITTED",
+            "5 INT. E",
+        ];
+        assert_eq!(numbers(&screenplay), expected);
+    }
+
+    #[test]
+    fn fills_only_gaps_between_numbers() {
+        let src = "Scene Numbers: Locked\n\nINT. A #2024#";
+        let mut screenplay = parse(src);
+        screenplay.number_scenes();
+        assert_eq!(numbers(&screenplay), ["2024 INT. A"]);
+
+        let src = "Scene Numbers: Locked\n\nINT. A #3#\n\nINT. B\n\nINT. C #A5#\n\n\
+                   INT. D #6#\n\nINT. E #7A#";
+        let mut screenplay = parse(src);
+        assert!(screenplay.number_scenes().is_empty());
+        let expected = [
+            "3 INT. A",
+            "3A INT. B",
+            "4 OMITTED",
+            "A5 INT. C",
+            "5 OMITTED",
+            "6 INT. D",
+            "7 OMITTED",
+            "7A INT. E",
+        ];
+        assert_eq!(numbers(&screenplay), expected);
+    }
+
+    #[test]
+    fn records_lock_on_titlepage() {
+        let mut screenplay = parse("INT. A");
+        screenplay.lock_scene_numbers();
+
+        let titlepage = screenplay.titlepage.as_ref().unwrap();
+        assert_eq!(titlepage.get("scene numbers"), Some(&["Locked".into()][..]));
+
+        screenplay.unlock_scene_numbers();
+        assert_eq!(screenplay, parse("INT. A #1#"));
+    }
+
+    #[test]
+    fn orders_numbers() {
+        let parsed = |s: &str| s.parse::<Number>().unwrap();
+        let mut numbers = ["12A", "B12", "11", "12", "A12", "12AA", "12B"].map(parsed);
+        numbers.sort();
+
+        let expected = ["11", "A12", "B12", "12", "12A", "12B", "12AA"].map(parsed);
+        assert_eq!(numbers, expected);
+        assert!("I-1".parse::<Number>().is_err() && "A1A".parse::<Number>().is_err());
+        assert_eq!(next_letters("AZ"), "BA");
+        assert_eq!(next_letters("ZZ"), "AAA");
+        assert_eq!(next_letters(""), "A");
+    }
+}
@@ -0,0 +1,241 @@
+use std::ops::Range;
+
+use crate::rich_string::RichString;
+use crate::screenplay::{Element, Screenplay};
+use crate::span::Spanned;
+
+/// A scene of a [Screenplay], as a view of the elements from a scene heading up to the next
+/// scene heading or section. See [Screenplay::scenes].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Scene<'a> {
+    /// The position of the scene among all scenes of the [Screenplay], starting at 0.
+    pub index: usize,
+    /// The slug of the scene heading.
+    pub heading: &'a RichString,
+    /// The scene number, if the heading has one (`#12A#`).
+    pub number: Option<&'a str>,
+    /// The elements of the scene after its heading.
+    pub body: &'a [Spanned<Element>],
+    /// The titles of the sections the scene is in, outermost first.
+    pub sections: Vec<&'a RichString>,
+    /// The range of the scene, heading included, in [Screenplay::elements].
+    pub range: Range<usize>,
+}
+
+impl Screenplay {
+    /// The scenes of the [Screenplay], in order.
+    ///
+    /// A scene starts with a scene heading and goes on up to the next scene heading or
+    /// section, so elements before the first scene heading, or between a section and the scene
+    /// heading following it, are not part of any scene.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustwell::parse;
+    ///
+    /// let src = "# Act One\n\nINT. HOUSE - DAY #1#\n\nBOB\nHi.\n\nEXT. GARDEN - NIGHT\n\nRain.";
+    /// let screenplay = parse(src);
+    /// let scenes = screenplay.scenes();
+    ///
+    /// assert_eq!(scenes.len(), 2);
+    /// assert_eq!(scenes[0].number, Some("1"));
+    /// assert_eq!(scenes[0].body.len(), 1);
+    /// assert_eq!(scenes[1].heading.plain_text(), "EXT. GARDEN - NIGHT");
+    /// assert_eq!(scenes[1].sections[0].plain_text(), "Act One");
+    /// ```
+    pub fn scenes(&self) -> Vec<Scene<'_>> {
+        let mut sections: Vec<(usize, &RichString)> = Vec::new();
+        let mut scenes = Vec::new();
+        for (i, element) in self.elements.iter().enumerate() {
+            match &element.node {
+                Element::Section { depth, title } => {
+                    sections.retain(|(d, _)| d < depth);
+                    sections.push((*depth, title));
+                }
+                Element::Heading { slug, number } => {
+                    let range = self.scene_range(i);
+                    scenes.push(Scene {
+                        index: scenes.len(),
+                        heading: slug,
+                        number: number.as_deref(),
+                        body: &self.elements[range.start + 1..range.end],
+                        sections: sections.iter().map(|(_, title)| *title).collect(),
+                        range,
+                    });
+                }
+                _ => (),
+            }
+        }
+        scenes
+    }
+
+    /// Removes scene `index` from the [Screenplay], and returns its elements, heading first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove_scene(&mut self, index: usize) -> Vec<Spanned<Element>> {
+        let range = self.scene_ranges()[index].clone();
+        self.elements.drain(range).collect()
+    }
+
+    /// Inserts a scene, so that it becomes scene `index`. The scene is inserted right before the
+    /// heading of the scene now at `index`, or after the last scene if `index` is the number of
+    /// scenes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of scenes, or if `scene` does not start with
+    /// a scene heading.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustwell::parse;
+    ///
+    /// let mut screenplay = parse("INT. HOUSE - DAY\n\nBOB\nHi.\n\nEXT. GARDEN - NIGHT");
+    /// let scene = screenplay.remove_scene(0);
+    /// screenplay.insert_scene(1, scene);
+    ///
+    /// assert_eq!(screenplay, parse("EXT. GARDEN - NIGHT\n\nINT. HOUSE - DAY\n\nBOB\nHi."));
+    /// ```
+    pub fn insert_scene(&mut self, index: usize, scene: Vec<Spanned<Element>>) {
+        assert!(
+            matches!(
+                scene.first().map(|e| &e.node),
+                Some(Element::Heading { .. })
+            ),
+            "a scene must start with a scene heading"
+        );
+
+        let ranges = self.scene_ranges();
+        let at = match ranges.get(index) {
+            Some(range) => range.start,
+            None if index == ranges.len() => ranges.last().map_or(self.elements.len(), |r| r.end),
+            None => panic!(
+                "scene index {index} out of bounds for {} scenes",
+                ranges.len()
+            ),
+        };
+        self.elements.splice(at..at, scene);
+    }
+
+    /// Moves scene `from`, so that it becomes scene `to`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `from` or `to` is out of bounds.
+    pub fn move_scene(&mut self, from: usize, to: usize) {
+        let count = self.scene_ranges().len();
+        assert!(
+            to < count,
+            "scene index {to} out of bounds for {count} scenes"
+        );
+
+        let scene = self.remove_scene(from);
+        self.insert_scene(to, scene);
+    }
+
+    /// The ranges of all scenes in [Screenplay::elements].
+    pub(super) fn scene_ranges(&self) -> Vec<Range<usize>> {
+        (0..self.elements.len())
+            .filter(|&i| matches!(self.elements[i].node, Element::Heading { .. }))
+            .map(|i| self.scene_range(i))
+            .collect()
+    }
+
+    /// The range of the scene with its heading at `start`.
+    fn scene_range(&self, start: usize) -> Range<usize> {
+        let len = self.elements[start + 1..]
+            .iter()
+            .take_while(|e| !matches!(e.node, Element::Heading { .. } | Element::Section { .. }))
+            .count();
+        start..start + 1 + len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse;
+
+    const SRC: &str = "Title: Fish\n\nFADE IN:\n\n# Act One\n\n## Morning\n\n\
+                       INT. HOUSE - DAY #1#\n\nBob wakes.\n\n\
+                       INT. KITCHEN - DAY #2#\n\nBOB\nCoffee.\n\n\
+                       ## Evening\n\n= Things go wrong.\n\nEXT. GARDEN - NIGHT #3#\n\n\
+                       # Act Two\n\nINT. CAR - NIGHT #4#\n\nThey drive.";
+
+    #[test]
+    fn groups_scenes_with_sections() {
+        let screenplay = parse(SRC);
+        let scenes = screenplay.scenes();
+
+        let numbers: Vec<_> = scenes.iter().map(|s| s.number.unwrap()).collect();
+        assert_eq!(numbers, ["1", "2", "3", "4"]);
+        assert_eq!(scenes[2].index, 2);
+
+        let sections = |i: usize| -> Vec<String> {
+            scenes[i].sections.iter().map(|s| s.plain_text()).collect()
+        };
+        assert_eq!(sections(0), ["Act One", "Morning"]);
+        assert_eq!(sections(1), ["Act One", "Morning"]);
+        assert_eq!(sections(2), ["Act One", "Evening"]);
+        assert_eq!(sections(3), ["Act Two"]);
+
+        // The synopsis of the section is not a part of any scene
+        assert!(scenes[1].body.len() == 1 && scenes[2].body.is_empty());
+        assert_eq!(
+            scenes[3].body[0].node,
+            parse("They drive.").elements[0].node
+        );
+        assert_eq!(scenes[3].range, 11..13);
+    }
+
+    #[test]
+    fn removes_scenes() {
+        let mut screenplay = parse(SRC);
+        let removed = screenplay.remove_scene(1);
+
+        assert_eq!(removed.len(), 2);
+        assert_eq!(
+            screenplay
+                .scenes()
+                .iter()
+                .map(|s| s.number)
+                .collect::<Vec<_>>(),
+            [Some("1"), Some("3"), Some("4")]
+        );
+    }
+
+    #[test]
+    fn moves_scenes_across_sections() {
+        let mut screenplay = parse(SRC);
+        screenplay.move_scene(0, 3);
+        screenplay.move_scene(3, 2);
+
+        let scenes = screenplay.scenes();
+        let numbers: Vec<_> = scenes.iter().map(|s| s.number.unwrap()).collect();
+        assert_eq!(numbers, ["2", "3", "1", "4"]);
+        assert_eq!(scenes[2].sections[0].plain_text(), "Act Two");
+        assert_eq!(scenes[2].body.len(), 1);
+    }
+
+    #[test]
+    fn inserts_after_last_scene() {
+        let mut screenplay = parse("INT. HOUSE - DAY\n\n# Epilogue");
+        let scene = parse("EXT. SEA - DAY\n\nWaves.").elements;
+        screenplay.insert_scene(1, scene);
+
+        assert_eq!(
+            screenplay,
+            parse("INT. HOUSE - DAY\n\nEXT. SEA - DAY\n\nWaves.\n\n# Epilogue")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "must start with a scene heading")]
+    fn panics_on_scene_without_heading() {
+        let mut screenplay = parse("INT. HOUSE - DAY");
+        screenplay.insert_scene(0, parse("Waves.").elements);
+    }
+}
@@ -0,0 +1,511 @@
+//! This module implements traversal of the [Screenplay] AST, through the [Visit] and [VisitMut]
+//! traits.
+//!
+//! Both traits have a method for every kind of node, down to the single runs of a
+//! [RichString]. By default every method walks into the children of its node, so an
+//! implementation only needs to override the methods for the nodes it cares about. An overriding
+//! method can keep walking into the children by calling the matching `walk_*` function.
+//!
+//! Elements and dialogue elements are visited together with the [Span](crate::span::Span) they
+//! were parsed from, so a visitor can point back into the source.
+//!
+//! # Examples
+//!
+//! Counting the words of all dialogue:
+//!
+//! ```
+//! use rustwell::parse;
+//! use rustwell::screenplay::{DialogueElement, Visit};
+//! use rustwell::span::Spanned;
+//!
+//! struct Words(usize);
+//!
+//! impl Visit for Words {
+//!     fn visit_dialogue_element(&mut self, element: &Spanned<DialogueElement>) {
+//!         if let DialogueElement::Line(line) = &element.node {
+//!             self.0 += line.plain_text().split_whitespace().count();
+//!         }
+//!     }
+//! }
+//!
+//! let screenplay = parse("INT. HOUSE - DAY\n\nBOB\nHello there.\n\nALICE\n(sighs)\nHi.");
+//! let mut words = Words(0);
+//! words.visit_screenplay(&screenplay);
+//!
+//! assert_eq!(words.0, 3);
+//! ```
+//!
+//! Writing all cues in upper case and leaving out synopses:
+//!
+//! ```
+//! use rustwell::parse;
+//! use rustwell::screenplay::{Dialogue, Element, Screenplay, VisitMut, visit};
+//!
+//! struct Tidy;
+//!
+//! impl VisitMut for Tidy {
+//!     fn visit_screenplay_mut(&mut self, screenplay: &mut Screenplay) {
+//!         screenplay.elements.retain(|e| !matches!(e.node, Element::Synopsis(_)));
+//!         visit::walk_screenplay_mut(self, screenplay);
+//!     }
+//!
+//!     fn visit_dialogue_mut(&mut self, dialogue: &mut Dialogue) {
+//!         for run in &mut dialogue.character.elements {
+//!             run.text = run.text.to_uppercase();
+//!         }
+//!         visit::walk_dialogue_mut(self, dialogue);
+//!     }
+//! }
+//!
+//! let mut screenplay = parse("= The reveal.\n\n@McCLANE\nYippee ki-yay.");
+//! Tidy.visit_screenplay_mut(&mut screenplay);
+//!
+//! let Element::Dialogue(dialogue) = &screenplay.elements[0].node else {
+//!     panic!("expected dialogue");
+//! };
+//! assert_eq!(screenplay.elements.len(), 1);
+//! assert_eq!(dialogue.character.plain_text(), "MCCLANE");
+//! ```
+
+use crate::rich_string::{self, RichString};
+use crate::screenplay::{Dialogue, DialogueElement, Element, Screenplay, TitlePage};
+use crate::span::Spanned;
+
+/// A traversal of a [Screenplay] by reference. See the [module](self) documentation.
+pub trait Visit {
+    fn visit_screenplay(&mut self, screenplay: &Screenplay) {
+        walk_screenplay(self, screenplay);
+    }
+
+    fn visit_titlepage(&mut self, titlepage: &TitlePage) {
+        walk_titlepage(self, titlepage);
+    }
+
+    /// Visits any [Element], and by default passes it on to the method of its kind.
+    fn visit_element(&mut self, element: &Spanned<Element>) {
+        walk_element(self, element);
+    }
+
+    fn visit_section(&mut self, _depth: usize, title: &RichString) {
+        self.visit_rich_string(title);
+    }
+
+    fn visit_heading(&mut self, slug: &RichString, _number: Option<&str>) {
+        self.visit_rich_string(slug);
+    }
+
+    fn visit_action(&mut self, action: &RichString) {
+        self.visit_rich_string(action);
+    }
+
+    fn visit_dialogue(&mut self, dialogue: &Dialogue) {
+        walk_dialogue(self, dialogue);
+    }
+
+    fn visit_dual_dialogue(&mut self, first: &Spanned<Dialogue>, second: &Spanned<Dialogue>) {
+        self.visit_dialogue(first);
+        self.visit_dialogue(second);
+    }
+
+    /// Visits any [DialogueElement], and by default passes it on to the method of its kind.
+    fn visit_dialogue_element(&mut self, element: &Spanned<DialogueElement>) {
+        walk_dialogue_element(self, element);
+    }
+
+    fn visit_parenthetical(&mut self, parenthetical: &RichString) {
+        self.visit_rich_string(parenthetical);
+    }
+
+    fn visit_line(&mut self, line: &RichString) {
+        self.visit_rich_string(line);
+    }
+
+    fn visit_lyrics(&mut self, lyrics: &RichString) {
+        self.visit_rich_string(lyrics);
+    }
+
+    fn visit_transition(&mut self, transition: &RichString) {
+        self.visit_rich_string(transition);
+    }
+
+    fn visit_centered_text(&mut self, text: &RichString) {
+        self.visit_rich_string(text);
+    }
+
+    fn visit_synopsis(&mut self, synopsis: &RichString) {
+        self.visit_rich_string(synopsis);
+    }
+
+    fn visit_note(&mut self, note: &RichString) {
+        self.visit_rich_string(note);
+    }
+
+    fn visit_boneyard(&mut self, _boneyard: &str) {}
+
+    fn visit_page_break(&mut self) {}
+
+    fn visit_rich_string(&mut self, rich_string: &RichString) {
+        walk_rich_string(self, rich_string);
+    }
+
+    /// Visits a single run of a [RichString], including notes and boneyards within it.
+    fn visit_run(&mut self, _run: &rich_string::Element) {}
+}
+
+/// A traversal of a [Screenplay] by mutable reference. See the [module](self) documentation.
+pub trait VisitMut {
+    fn visit_screenplay_mut(&mut self, screenplay: &mut Screenplay) {
+        walk_screenplay_mut(self, screenplay);
+    }
+
+    fn visit_titlepage_mut(&mut self, titlepage: &mut TitlePage) {
+        walk_titlepage_mut(self, titlepage);
+    }
+
+    /// Visits any [Element], and by default passes it on to the method of its kind.
+    fn visit_element_mut(&mut self, element: &mut Spanned<Element>) {
+        walk_element_mut(self, element);
+    }
+
+    fn visit_section_mut(&mut self, _depth: &mut usize, title: &mut RichString) {
+        self.visit_rich_string_mut(title);
+    }
+
+    fn visit_heading_mut(&mut self, slug: &mut RichString, _number: &mut Option<String>) {
+        self.visit_rich_string_mut(slug);
+    }
+
+    fn visit_action_mut(&mut self, action: &mut RichString) {
+        self.visit_rich_string_mut(action);
+    }
+
+    fn visit_dialogue_mut(&mut self, dialogue: &mut Dialogue) {
+        walk_dialogue_mut(self, dialogue);
+    }
+
+    fn visit_dual_dialogue_mut(
+        &mut self,
+        first: &mut Spanned<Dialogue>,
+        second: &mut Spanned<Dialogue>,
+    ) {
+        self.visit_dialogue_mut(first);
+        self.visit_dialogue_mut(second);
+    }
+
+    /// Visits any [DialogueElement], and by default passes it on to the method of its kind.
+    fn visit_dialogue_element_mut(&mut self, element: &mut Spanned<DialogueElement>) {
+        walk_dialogue_element_mut(self, element);
+    }
+
+    fn visit_parenthetical_mut(&mut self, parenthetical: &mut RichString) {
+        self.visit_rich_string_mut(parenthetical);
+    }
+
+    fn visit_line_mut(&mut self, line: &mut RichString) {
+        self.visit_rich_string_mut(line);
+    }
+
+    fn visit_lyrics_mut(&mut self, lyrics: &mut RichString) {
+        self.visit_rich_string_mut(lyrics);
+    }
+
+    fn visit_transition_mut(&mut self, transition: &mut RichString) {
+        self.visit_rich_string_mut(transition);
+    }
+
+    fn visit_centered_text_mut(&mut self, text: &mut RichString) {
+        self.visit_rich_string_mut(text);
+    }
+
+    fn visit_synopsis_mut(&mut self, synopsis: &mut RichString) {
+        self.visit_rich_string_mut(synopsis);
+    }
+
+    fn visit_note_mut(&mut self, note: &mut RichString) {
+        self.visit_rich_string_mut(note);
+    }
+
+    fn visit_boneyard_mut(&mut self, _boneyard: &mut String) {}
+
+    fn visit_page_break_mut(&mut self) {}
+
+    fn visit_rich_string_mut(&mut self, rich_string: &mut RichString) {
+        walk_rich_string_mut(self, rich_string);
+    }
+
+    /// Visits a single run of a [RichString], including notes and boneyards within it.
+    fn visit_run_mut(&mut self, _run: &mut rich_string::Element) {}
+}
+
+/// Visits the title page, if any, and then every element of a [Screenplay].
+pub fn walk_screenplay<V: Visit + ?Sized>(visitor: &mut V, screenplay: &Screenplay) {
+    if let Some(titlepage) = &screenplay.titlepage {
+        visitor.visit_titlepage(titlepage);
+    }
+    for element in &screenplay.elements {
+        visitor.visit_element(element);
+    }
+}
+
+/// Visits every value of a [TitlePage], in the order they are written.
+pub fn walk_titlepage<V: Visit + ?Sized>(visitor: &mut V, titlepage: &TitlePage) {
+    let fields = [
+        &titlepage.title,
+        &titlepage.credit,
+        &titlepage.authors,
+        &titlepage.source,
+        &titlepage.draft_date,
+        &titlepage.contact,
+    ];
+    let extra = titlepage.extra.iter().map(|(_, values)| values);
+    for value in fields.into_iter().chain(extra).flatten() {
+        visitor.visit_rich_string(value);
+    }
+}
+
+/// Passes an [Element] on to the method of its kind.
+pub fn walk_element<V: Visit + ?Sized>(visitor: &mut V, element: &Spanned<Element>) {
+    match &element.node {
+        Element::Section { depth, title } => visitor.visit_section(*depth, title),
+        Element::Heading { slug, number } => visitor.visit_heading(slug, number.as_deref()),
+        Element::Action(s) => visitor.visit_action(s),
+        Element::Dialogue(d) => visitor.visit_dialogue(d),
+        Element::DualDialogue(d1, d2) => visitor.visit_dual_dialogue(d1, d2),
+        Element::Lyrics(s) => visitor.visit_lyrics(s),
+        Element::Transition(s) => visitor.visit_transition(s),
+        Element::CenteredText(s) => visitor.visit_centered_text(s),
+        Element::Synopsis(s) => visitor.visit_synopsis(s),
+        Element::Note(s) => visitor.visit_note(s),
+        Element::Boneyard(s) => visitor.visit_boneyard(s),
+        Element::PageBreak => visitor.visit_page_break(),
+    }
+}
+
+/// Visits the character, the extension and then every element of a [Dialogue].
+pub fn walk_dialogue<V: Visit + ?Sized>(visitor: &mut V, dialogue: &Dialogue) {
+    visitor.visit_rich_string(&dialogue.character);
+    if let Some(extension) = &dialogue.extension {
+        visitor.visit_rich_string(extension);
+    }
+    for element in &dialogue.elements {
+        visitor.visit_dialogue_element(element);
+    }
+}
+
+/// Passes a [DialogueElement] on to the method of its kind.
+pub fn walk_dialogue_element<V: Visit + ?Sized>(
+    visitor: &mut V,
+    element: &Spanned<DialogueElement>,
+) {
+    match &element.node {
+        DialogueElement::Parenthetical(s) => visitor.visit_parenthetical(s),
+        DialogueElement::Line(s) => visitor.visit_line(s),
+    }
+}
+
+/// Visits every run of a [RichString].
+pub fn walk_rich_string<V: Visit + ?Sized>(visitor: &mut V, rich_string: &RichString) {
+    for run in &rich_string.elements {
+        visitor.visit_run(run);
+    }
+}
+
+/// Visits the title page, if any, and then every element of a [Screenplay].
+pub fn walk_screenplay_mut<V: VisitMut + ?Sized>(visitor: &mut V, screenplay: &mut Screenplay) {
+    if let Some(titlepage) = &mut screenplay.titlepage {
+        visitor.visit_titlepage_mut(titlepage);
+    }
+    for element in &mut screenplay.elements {
+        visitor.visit_element_mut(element);
+    }
+}
+
+/// Visits every value of a [TitlePage], in the order they are written.
+pub fn walk_titlepage_mut<V: VisitMut + ?Sized>(visitor: &mut V, titlepage: &mut TitlePage) {
+    let fields = [
+        &mut titlepage.title,
+        &mut titlepage.credit,
+        &mut titlepage.authors,
+        &mut titlepage.source,
+        &mut titlepage.draft_date,
+        &mut titlepage.contact,
+    ];
+    let extra = titlepage.extra.iter_mut().map(|(_, values)| values);
+    for value in fields.into_iter().chain(extra).flatten() {
+        visitor.visit_rich_string_mut(value);
+    }
+}
+
+/// Passes an [Element] on to the method of its kind.
+pub fn walk_element_mut<V: VisitMut + ?Sized>(visitor: &mut V, element: &mut Spanned<Element>) {
+    match &mut element.node {
+        Element::Section { depth, title } => visitor.visit_section_mut(depth, title),
+        Element::Heading { slug, number } => visitor.visit_heading_mut(slug, number),
+        Element::Action(s) => visitor.visit_action_mut(s),
+        Element::Dialogue(d) => visitor.visit_dialogue_mut(d),
+        Element::DualDialogue(d1, d2) => visitor.visit_dual_dialogue_mut(d1, d2),
+        Element::Lyrics(s) => visitor.visit_lyrics_mut(s),
+        Element::Transition(s) => visitor.visit_transition_mut(s),
+        Element::CenteredText(s) => visitor.visit_centered_text_mut(s),
+        Element::Synopsis(s) => visitor.visit_synopsis_mut(s),
+        Element::Note(s) => visitor.visit_note_mut(s),
+        Element::Boneyard(s) => visitor.visit_boneyard_mut(s),
+        Element::PageBreak => visitor.visit_page_break_mut(),
+    }
+}
+
+/// Visits the character, the extension and then every element of a [Dialogue].
+pub fn walk_dialogue_mut<V: VisitMut + ?Sized>(visitor: &mut V, dialogue: &mut Dialogue) {
+    visitor.visit_rich_string_mut(&mut dialogue.character);
+    if let Some(extension) = &mut dialogue.extension {
+        visitor.visit_rich_string_mut(extension);
+    }
+    for element in &mut dialogue.elements {
+        visitor.visit_dialogue_element_mut(element);
+    }
+}
+
+/// Passes a [DialogueElement] on to the method of its kind.
+pub fn walk_dialogue_element_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    element: &mut Spanned<DialogueElement>,
+) {
+    match &mut element.node {
+        DialogueElement::Parenthetical(s) => visitor.visit_parenthetical_mut(s),
+        DialogueElement::Line(s) => visitor.visit_line_mut(s),
+    }
+}
+
+/// Visits every run of a [RichString].
+pub fn walk_rich_string_mut<V: VisitMut + ?Sized>(visitor: &mut V, rich_string: &mut RichString) {
+    for run in &mut rich_string.elements {
+        visitor.visit_run_mut(run);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseOptions;
+
+    /// Records the kinds of nodes in the order they are visited.
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl Visit for Recorder {
+        fn visit_element(&mut self, element: &Spanned<Element>) {
+            self.0.push(
+                format!("{:?}", element.node)
+                    .split(['(', ' '])
+                    .next()
+                    .unwrap()
+                    .to_string(),
+            );
+            walk_element(self, element);
+        }
+
+        fn visit_dialogue_element(&mut self, element: &Spanned<DialogueElement>) {
+            self.0.push("DialogueElement".to_string());
+            walk_dialogue_element(self, element);
+        }
+
+        fn visit_boneyard(&mut self, boneyard: &str) {
+            self.0.push(format!("boneyard {boneyard}"));
+        }
+
+        fn visit_run(&mut self, run: &rich_string::Element) {
+            self.0.push(run.text.clone());
+        }
+    }
+
+    #[test]
+    fn visits_in_order() {
+        let src = "Title: Fish\n\n# Act\n\nINT. HOUSE - DAY\n\n\
+                   BOB (V.O.)\n(quietly)\nHi **there**.\n\n\
+                   ALICE ^\nHey.\n\n/*cut*/\n\n===";
+        let screenplay = crate::parse_with_options(
+            src,
+            ParseOptions {
+                boneyards: true,
+                ..Default::default()
+            },
+        );
+        let mut recorder = Recorder::default();
+        recorder.visit_screenplay(&screenplay);
+
+        let expected = [
+            "Fish",
+            "Section",
+            "Act",
+            "Heading",
+            "INT. HOUSE - DAY",
+            "DualDialogue",
+            "BOB",
+            "V.O.",
+            "DialogueElement",
+            "(quietly)",
+            "DialogueElement",
+            "Hi ",
+            "there",
+            ".",
+            "ALICE",
+            "DialogueElement",
+            "Hey.",
+            "Boneyard",
+            "boneyard cut",
+            "PageBreak",
+        ];
+        assert_eq!(recorder.0, expected);
+    }
+
+    /// Records the source of every element and dialogue element.
+    struct Sources<'a>(&'a str, Vec<&'a str>);
+
+    impl Visit for Sources<'_> {
+        fn visit_element(&mut self, element: &Spanned<Element>) {
+            self.1.push(&self.0[element.span.range()]);
+            walk_element(self, element);
+        }
+
+        fn visit_dialogue_element(&mut self, element: &Spanned<DialogueElement>) {
+            self.1.push(&self.0[element.span.range()]);
+        }
+    }
+
+    #[test]
+    fn visits_spans() {
+        let src = "INT. HOUSE - DAY\n\nBOB\n(quietly)\nHi.";
+        let mut sources = Sources(src, Vec::new());
+        sources.visit_screenplay(&crate::parse(src));
+
+        let expected = [
+            "INT. HOUSE - DAY",
+            "BOB\n(quietly)\nHi.",
+            "(quietly)",
+            "Hi.",
+        ];
+        assert_eq!(sources.1, expected);
+    }
+
+    struct Uppercase;
+
+    impl VisitMut for Uppercase {
+        fn visit_heading_mut(&mut self, _slug: &mut RichString, number: &mut Option<String>) {
+            *number = None;
+        }
+
+        fn visit_run_mut(&mut self, run: &mut rich_string::Element) {
+            run.text = run.text.to_uppercase();
+        }
+    }
+
+    #[test]
+    fn mutates_overridden_nodes_only() {
+        let mut screenplay =
+            crate::parse("Title: fish\n\nINT. house #1#\n\nBOB\nhi [[note]]\n\n> the end <");
+        Uppercase.visit_screenplay_mut(&mut screenplay);
+
+        let expected = crate::parse("Title: FISH\n\nINT. house\n\nBOB\nHI [[NOTE]]\n\n> THE END <");
+        assert_eq!(screenplay, expected);
+    }
+}
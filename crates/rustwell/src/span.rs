@@ -0,0 +1,126 @@
+//! This module implements source positions for the [Screenplay](crate::Screenplay) AST, so that
+//! every node can be traced back to where it was written in the original *Fountain* text.
+//!
+//! # Examples
+//!
+//! ```
+//! use rustwell::parse;
+//!
+//! let screenplay = parse("INT. HOUSE - DAY\n\nThe door is open.");
+//! let action = &screenplay.elements[1];
+//!
+//! assert_eq!(action.span.start.line, 3);
+//! assert_eq!(action.span.start.column, 1);
+//! assert_eq!(action.span.range(), 18..35);
+//! ```
+
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut, Range};
+
+/// A single position in the original source text.
+///
+/// A default [Position] (all zeroes) is used for nodes that were not produced by the parser.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Position {
+    /// The byte offset into the source.
+    pub offset: usize,
+    /// The line number, starting at 1.
+    pub line: usize,
+    /// The column, counted in characters and starting at 1.
+    pub column: usize,
+}
+
+/// A region of the original source text, from `start` up to, but not including, `end`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    /// Creates a new [Span] between two [Position]s.
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+
+    /// The byte range of the [Span] in the original source.
+    pub fn range(&self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+
+    /// If the [Span] covers no text at all.
+    pub fn is_empty(&self) -> bool {
+        self.start.offset == self.end.offset
+    }
+
+    /// Returns the smallest [Span] covering both `self` and `other`.
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+/// A node of the AST together with the [Span] it was parsed from.
+///
+/// The [Span] is positional metadata only and never takes part in equality or hashing, so two
+/// nodes compare equal whenever their contents do, no matter where they were written.
+/// [Spanned] dereferences to the node it wraps.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spanned<T> {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub node: T,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    /// Wraps a node together with its [Span].
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+
+    /// Unwraps the node, discarding the [Span].
+    pub fn into_inner(self) -> T {
+        self.node
+    }
+}
+
+impl<T> From<T> for Spanned<T> {
+    /// Wraps a node with a default [Span], for nodes that were not parsed from any source.
+    fn from(node: T) -> Self {
+        Self::new(node, Span::default())
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl<T> DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.node
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+impl<T: Eq> Eq for Spanned<T> {}
+
+impl<T: Hash> Hash for Spanned<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.node.hash(state)
+    }
+}
@@ -0,0 +1,350 @@
+//! This module implements [Stats] for a [Screenplay], like word counts, scene counts and how
+//! much each character speaks.
+//!
+//! # Examples
+//!
+//! ```
+//! use rustwell::parse;
+//! use rustwell::stats::Stats;
+//!
+//! let src = "INT. HOUSE - DAY\n\nBob waits.\n\nBOB\nWhere is she?\n\nEXT. GARDEN - NIGHT\n\n\
+//!            ALICE\nRight here.\n\nBOB\nOh.";
+//! let stats = Stats::new(&parse(src));
+//!
+//! assert_eq!(stats.scenes, 2);
+//! assert_eq!(stats.settings.interior, 1);
+//! assert_eq!(stats.times.night, 1);
+//! assert_eq!(stats.words.action, 2);
+//! assert_eq!(stats.words.dialogue, 6);
+//! assert_eq!(stats.characters[0].name, "BOB");
+//! assert_eq!(stats.characters[0].lines, 2);
+//! assert_eq!(stats.characters[0].words, 4);
+//! ```
+
+use crate::rich_string::RichString;
+use crate::screenplay::{Dialogue, DialogueElement, SceneHeading, Screenplay, Setting, Visit};
+
+/// The number of speeches kept in [Stats::longest_speeches].
+const LONGEST_SPEECHES: usize = 5;
+
+/// Words in the time of a scene heading counting as day.
+const DAY: [&str; 6] = ["DAY", "MORNING", "AFTERNOON", "NOON", "DAWN", "SUNRISE"];
+
+/// Words in the time of a scene heading counting as night.
+const NIGHT: [&str; 5] = ["NIGHT", "EVENING", "MIDNIGHT", "DUSK", "SUNSET"];
+
+/// Statistics of a [Screenplay]. Notes and boneyards are never counted.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    /// The words of each kind of element.
+    pub words: Words,
+    /// The number of scenes.
+    pub scenes: usize,
+    /// The number of scenes in each kind of [Setting].
+    pub settings: Settings,
+    /// The number of scenes at each time of day.
+    pub times: Times,
+    /// Every character that speaks, with the ones speaking the most words first.
+    pub characters: Vec<Character>,
+    /// The longest speeches, longest first.
+    pub longest_speeches: Vec<Speech>,
+}
+
+/// The number of words of each kind of element.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Words {
+    pub headings: usize,
+    pub action: usize,
+    /// The lines of dialogue, not counting character names and parentheticals.
+    pub dialogue: usize,
+    pub parentheticals: usize,
+    pub lyrics: usize,
+    pub transitions: usize,
+    pub centered: usize,
+    pub sections: usize,
+    pub synopses: usize,
+}
+
+impl Words {
+    /// The number of words in the printed screenplay, which leaves out sections and synopses.
+    pub fn total(&self) -> usize {
+        self.headings
+            + self.action
+            + self.dialogue
+            + self.parentheticals
+            + self.lyrics
+            + self.transitions
+            + self.centered
+    }
+}
+
+/// The number of scenes in each kind of [Setting].
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Settings {
+    /// `INT.`
+    pub interior: usize,
+    /// `EXT.`
+    pub exterior: usize,
+    /// `INT./EXT.`, `EXT./INT.` and the like.
+    pub both: usize,
+    /// Establishing shots and scene headings without a setting.
+    pub other: usize,
+}
+
+/// The number of scenes at each time of day.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Times {
+    /// `DAY`, `MORNING`, `AFTERNOON`, `NOON`, `DAWN` and `SUNRISE`.
+    pub day: usize,
+    /// `NIGHT`, `EVENING`, `MIDNIGHT`, `DUSK` and `SUNSET`.
+    pub night: usize,
+    /// Any other time, like `CONTINUOUS`, or no time at all.
+    pub other: usize,
+}
+
+/// How much a character speaks.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Character {
+    /// The name of the character in upper case, without any extension.
+    pub name: String,
+    /// The number of lines, meaning the number of times the character speaks.
+    pub lines: usize,
+    /// The number of words of all lines of the character.
+    pub words: usize,
+}
+
+/// A single speech of a character.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Speech {
+    /// The name of the character in upper case, without any extension.
+    pub character: String,
+    /// The number of words of the speech.
+    pub words: usize,
+    /// The index of the scene the speech is in, if it is in one.
+    pub scene: Option<usize>,
+}
+
+impl Stats {
+    /// Computes the [Stats] of a [Screenplay].
+    pub fn new(screenplay: &Screenplay) -> Self {
+        let mut counter = Counter::default();
+        counter.visit_screenplay(screenplay);
+
+        let mut stats = counter.stats;
+        stats
+            .characters
+            .sort_by(|a, b| b.words.cmp(&a.words).then(b.lines.cmp(&a.lines)));
+        stats
+            .longest_speeches
+            .sort_by_key(|s| std::cmp::Reverse(s.words));
+        stats.longest_speeches.truncate(LONGEST_SPEECHES);
+        stats
+    }
+}
+
+/// Counts the words of a [RichString], leaving out notes, boneyards and lone punctuation like
+/// the dash in `INT. HOUSE - DAY`.
+fn words(s: &RichString) -> usize {
+    s.plain_text()
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+/// A [Visit] collecting the [Stats] in the order of the [Screenplay].
+#[derive(Default)]
+struct Counter {
+    stats: Stats,
+    /// The index of the scene being visited, ended by the next section like in
+    /// [Screenplay::scenes].
+    scene: Option<usize>,
+}
+
+impl Visit for Counter {
+    fn visit_section(&mut self, _depth: usize, title: &RichString) {
+        self.stats.words.sections += words(title);
+        self.scene = None;
+    }
+
+    fn visit_heading(&mut self, slug: &RichString, _number: Option<&str>) {
+        let stats = &mut self.stats;
+        self.scene = Some(stats.scenes);
+        stats.scenes += 1;
+        stats.words.headings += words(slug);
+
+        let heading = SceneHeading::parse(slug);
+        match heading.setting {
+            Some(Setting::Interior) => stats.settings.interior += 1,
+            Some(Setting::Exterior) => stats.settings.exterior += 1,
+            Some(Setting::InteriorExterior | Setting::ExteriorInterior) => stats.settings.both += 1,
+            Some(Setting::Establishing) | None => stats.settings.other += 1,
+        }
+
+        let time = heading.time.unwrap_or_default().to_ascii_uppercase();
+        let has = |words: &[&str]| {
+            time.split(|c: char| !c.is_alphanumeric())
+                .any(|word| words.contains(&word))
+        };
+        if has(&DAY) {
+            stats.times.day += 1;
+        } else if has(&NIGHT) {
+            stats.times.night += 1;
+        } else {
+            stats.times.other += 1;
+        }
+    }
+
+    fn visit_action(&mut self, action: &RichString) {
+        self.stats.words.action += words(action);
+    }
+
+    fn visit_dialogue(&mut self, dialogue: &Dialogue) {
+        let name = dialogue.character.plain_text().trim().to_uppercase();
+        let mut speech = 0;
+        for element in &dialogue.elements {
+            match &element.node {
+                DialogueElement::Parenthetical(s) => self.stats.words.parentheticals += words(s),
+                DialogueElement::Line(s) => speech += words(s),
+            }
+        }
+        self.stats.words.dialogue += speech;
+
+        let characters = &mut self.stats.characters;
+        let character = match characters.iter().position(|c| c.name == name) {
+            Some(i) => &mut characters[i],
+            None => {
+                characters.push(Character {
+                    name: name.clone(),
+                    ..Default::default()
+                });
+                characters.last_mut().unwrap()
+            }
+        };
+        character.lines += 1;
+        character.words += speech;
+
+        self.stats.longest_speeches.push(Speech {
+            character: name,
+            words: speech,
+            scene: self.scene,
+        });
+    }
+
+    fn visit_lyrics(&mut self, lyrics: &RichString) {
+        self.stats.words.lyrics += words(lyrics);
+    }
+
+    fn visit_transition(&mut self, transition: &RichString) {
+        self.stats.words.transitions += words(transition);
+    }
+
+    fn visit_centered_text(&mut self, text: &RichString) {
+        self.stats.words.centered += words(text);
+    }
+
+    fn visit_synopsis(&mut self, synopsis: &RichString) {
+        self.stats.words.synopses += words(synopsis);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn counts_words_per_kind() {
+        let src = "# Act One\n\n= Bob gets bad news.\n\nINT. HOUSE - DAY\n\n\
+                   Bob reads [[check the prop]] a letter.\n\nBOB\n(to himself)\nNo. No, no.\n\n\
+                   ~Sad song here\n\nCUT TO:\n\n> THE END <";
+        let words = Stats::new(&parse(src)).words;
+
+        let expected = Words {
+            headings: 3,
+            action: 4,
+            dialogue: 3,
+            parentheticals: 2,
+            lyrics: 3,
+            transitions: 2,
+            centered: 2,
+            sections: 2,
+            synopses: 4,
+        };
+        assert_eq!(words, expected);
+        assert_eq!(words.total(), 19);
+    }
+
+    #[test]
+    fn splits_settings_and_times() {
+        let src = "INT. HOUSE - DAY\n\nEXT. GARDEN - NIGHT\n\n\
+                   INT./EXT. CAR - MOVING - EARLY MORNING\n\nEST. CITY - DUSK\n\n\
+                   .FLASHBACK\n\nEXT. HOUSE - CONTINUOUS";
+        let stats = Stats::new(&parse(src));
+
+        let settings = Settings {
+            interior: 1,
+            exterior: 2,
+            both: 1,
+            other: 2,
+        };
+        let times = Times {
+            day: 2,
+            night: 2,
+            other: 2,
+        };
+        assert_eq!(stats.scenes, 6);
+        assert_eq!((stats.settings, stats.times), (settings, times));
+    }
+
+    #[test]
+    fn ranks_characters_and_speeches() {
+        let src = "ALICE\nOne two three.\n\nINT. HOUSE - DAY\n\n\
+                   @Bob\nOne.\n\nBOB (V.O.)\nOne two.\n\n\
+                   CAROL\nOne two three four five.\n\nALICE\nOne.\n(beat)\nTwo three four.\n\n\
+                   EXT. GARDEN - DAY\n\nBOB\nOne two three.\n\nDAVE ^\nOne.";
+        let stats = Stats::new(&parse(src));
+
+        let character = |name: &str, lines, words| Character {
+            name: name.to_string(),
+            lines,
+            words,
+        };
+        let expected = [
+            character("ALICE", 2, 7),
+            character("BOB", 3, 6),
+            character("CAROL", 1, 5),
+            character("DAVE", 1, 1),
+        ];
+        assert_eq!(stats.characters, expected);
+
+        let speech = |character: &str, words, scene| Speech {
+            character: character.to_string(),
+            words,
+            scene,
+        };
+        let expected = [
+            speech("CAROL", 5, Some(0)),
+            speech("ALICE", 4, Some(0)),
+            speech("ALICE", 3, None),
+            speech("BOB", 3, Some(1)),
+            speech("BOB", 2, Some(0)),
+        ];
+        assert_eq!(stats.longest_speeches, expected);
+    }
+    #[test]
+    fn ends_scenes_at_sections() {
+        let src = "INT. HOUSE - DAY\n\nALICE\nOne.\n\n# Act Two\n\nBOB\nOne two.";
+        let screenplay = parse(src);
+        let stats = Stats::new(&screenplay);
+
+        let scenes: Vec<_> = stats.longest_speeches.iter().map(|s| s.scene).collect();
+        assert_eq!(scenes, [None, Some(0)]);
+        assert_eq!(screenplay.scenes()[0].body.len(), 1);
+    }
+}